@@ -0,0 +1,383 @@
+use std::{env, fs, io, process::ExitCode, time::Duration};
+
+use serde::Serialize;
+use sokoban_rs::solve::{config::SolverConfig, solver::*, stats::SolverStats};
+use soukoban::{Actions, Level};
+
+const USAGE: &str = "\
+Usage: sokoban-solve [OPTIONS] [FILE]...
+
+Solves the levels of XSB/SOK files, or of the standard input if no file is given.
+
+Options:
+  -l, --level <INDEX|TITLE>     Only solve the level with the index (starting from 1)
+                                or title in each file, can be repeated
+  -s, --strategy <STRATEGY>     Search strategy [default: Fast]
+  -w, --weight <WEIGHT>         Weight of the lower bound of the `Weighted` strategy [default: 5]
+  -a, --anytime                 Keep improving the solution of the `Weighted` strategy
+                                until it is proven optimal or the time limit is reached
+  -b, --lower-bound <METHOD>    Lower bound calculation method [default: MinimumMove]
+  -t, --timeout <SECONDS>       Time limit of each level [default: 10]
+  -j, --threads <THREADS>       Number of threads used by the solver [default: 1]
+  -m, --memory-limit <MIB>      Maximum memory used by the solver, 0 means unlimited [default: 4096]
+  -f, --format <FORMAT>         Output format, `text` or `json` [default: text]
+  -h, --help                    Print help
+
+Exit status:
+  0  All levels are solved
+  1  Invalid arguments or input
+  2  A level is not solved within the time limit
+  3  A level has no solution
+  4  The solver runs out of memory on a level
+  5  The external solver can not be started
+If several levels are not solved, the highest status is returned.";
+
+/// Weight of the lower bound of the `Weighted` strategy if none is given.
+const DEFAULT_WEIGHT: u32 = 5;
+
+const STRATEGIES: [Strategy; 8] = [
+    Strategy::Fast,
+    Strategy::Mixed,
+    Strategy::OptimalMovePush,
+    Strategy::OptimalPushMove,
+    Strategy::IterativeDeepening,
+    Strategy::FeatureSpace,
+    Strategy::Beam,
+    Strategy::Weighted {
+        weight: DEFAULT_WEIGHT,
+    },
+];
+
+const LOWER_BOUND_METHODS: [LowerBoundMethod; 4] = [
+    LowerBoundMethod::MinimumPush,
+    LowerBoundMethod::MinimumMove,
+    LowerBoundMethod::ManhattanDistance,
+    LowerBoundMethod::MinimumMatching,
+];
+
+/// Options followed by a value.
+const VALUE_OPTIONS: [&str; 16] = [
+    "-l",
+    "--level",
+    "-s",
+    "--strategy",
+    "-w",
+    "--weight",
+    "-b",
+    "--lower-bound",
+    "-t",
+    "--timeout",
+    "-j",
+    "--threads",
+    "-m",
+    "--memory-limit",
+    "-f",
+    "--format",
+];
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Format {
+    Text,
+    Json,
+}
+
+struct Options {
+    files: Vec<String>,
+    levels: Vec<String>,
+    solver: SolverConfig,
+    /// Weight of the lower bound of the `Weighted` strategy.
+    weight: Option<u32>,
+    timeout: Duration,
+    format: Format,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            files: Vec::new(),
+            levels: Vec::new(),
+            solver: SolverConfig::default(),
+            weight: None,
+            timeout: Duration::from_secs(10),
+            format: Format::Text,
+        }
+    }
+}
+
+/// Result of solving a level.
+struct Report {
+    source: String,
+    index: usize,
+    title: Option<String>,
+    result: Result<Actions, SolveError>,
+    stats: SolverStats,
+}
+
+impl Report {
+    fn status(&self) -> &'static str {
+        match self.result {
+            Ok(_) => "solved",
+            Err(SolveError::Timeout) => "timeout",
+            Err(SolveError::NoSolution) => "no_solution",
+            Err(SolveError::OutOfMemory) => "out_of_memory",
+            Err(SolveError::SpawnFailed) => "spawn_failed",
+        }
+    }
+
+    fn exit_code(&self) -> u8 {
+        match self.result {
+            Ok(_) => 0,
+            Err(SolveError::Timeout) => 2,
+            Err(SolveError::NoSolution) => 3,
+            Err(SolveError::OutOfMemory) => 4,
+            Err(SolveError::SpawnFailed) => 5,
+        }
+    }
+
+    fn to_text(&self) -> String {
+        let mut text = format!("{} #{}", self.source, self.index);
+        if let Some(title) = &self.title {
+            text += &format!(" ({title})");
+        }
+        text += &format!("\nstatus: {}\n", self.status());
+        if let Ok(solution) = &self.result {
+            text += &format!(
+                "solution: {solution}\nmoves: {}, pushes: {}\n",
+                solution.moves(),
+                solution.pushes()
+            );
+        }
+        text += &format!(
+            "{}\nelapsed: {:.3} sec\n",
+            self.stats,
+            self.stats.elapsed.as_secs_f64()
+        );
+        text
+    }
+
+    fn to_json(&self) -> String {
+        let solution = self.result.as_ref().ok();
+        serde_json::to_string(&JsonReport {
+            source: &self.source,
+            index: self.index,
+            title: self.title.as_deref(),
+            status: self.status(),
+            solution: solution.map(ToString::to_string),
+            moves: solution.map(Actions::moves),
+            pushes: solution.map(Actions::pushes),
+            stats: JsonStats {
+                expanded_nodes: self.stats.expanded_nodes,
+                generated_nodes: self.stats.generated_nodes,
+                duplicates: self.stats.duplicates,
+                visited_nodes: self.stats.visited_nodes,
+                memory_usage: self.stats.memory_usage,
+                elapsed: self.stats.elapsed.as_secs_f64(),
+            },
+        })
+        .unwrap()
+    }
+}
+
+/// A [`Report`] as printed by `--format json`.
+#[derive(Serialize)]
+struct JsonReport<'a> {
+    source: &'a str,
+    index: usize,
+    title: Option<&'a str>,
+    status: &'static str,
+    solution: Option<String>,
+    moves: Option<usize>,
+    pushes: Option<usize>,
+    stats: JsonStats,
+}
+
+#[derive(Serialize)]
+struct JsonStats {
+    expanded_nodes: usize,
+    generated_nodes: usize,
+    duplicates: usize,
+    visited_nodes: usize,
+    memory_usage: usize,
+    /// Elapsed time in seconds.
+    elapsed: f64,
+}
+
+fn main() -> ExitCode {
+    let options = match parse_args(env::args().skip(1)) {
+        Ok(Some(options)) => options,
+        Ok(None) => {
+            println!("{USAGE}");
+            return ExitCode::SUCCESS;
+        }
+        Err(error) => {
+            eprintln!("error: {error}\n\n{USAGE}");
+            return ExitCode::from(1);
+        }
+    };
+
+    let mut inputs = Vec::new();
+    for file in &options.files {
+        let input = if file == "-" {
+            io::read_to_string(io::stdin()).map(|input| ("<stdin>".to_string(), input))
+        } else {
+            fs::read_to_string(file).map(|input| (file.clone(), input))
+        };
+        match input {
+            Ok(input) => inputs.push(input),
+            Err(error) => {
+                eprintln!("error: failed to read '{file}': {error}");
+                return ExitCode::from(1);
+            }
+        }
+    }
+
+    let mut levels = Vec::new();
+    for (source, input) in &inputs {
+        let file_levels = match Level::load_from_str(input).collect::<Result<Vec<_>, _>>() {
+            Ok(file_levels) => file_levels,
+            Err(error) => {
+                eprintln!("error: failed to parse '{source}': {error}");
+                return ExitCode::from(1);
+            }
+        };
+        for (index, level) in file_levels.into_iter().enumerate() {
+            let index = index + 1;
+            let title = level.metadata().get("title").cloned();
+            let selected = options.levels.is_empty()
+                || options.levels.iter().any(|selector| {
+                    selector.parse::<usize>().ok() == Some(index)
+                        || title.as_ref() == Some(selector)
+                });
+            if selected {
+                levels.push((source.clone(), index, title, level));
+            }
+        }
+    }
+    if levels.is_empty() {
+        eprintln!("error: no level is selected");
+        return ExitCode::from(1);
+    }
+
+    let mut exit_code = 0;
+    if options.format == Format::Json {
+        println!("[");
+    }
+    let count = levels.len();
+    for (i, (source, index, title, level)) in levels.into_iter().enumerate() {
+        let mut solver = options
+            .solver
+            .create_solver(level.map().clone(), Vec::new());
+        solver.start();
+        let result = match solver.step(options.timeout) {
+            // An anytime search returns its best solution once the time or memory is up.
+            Err(error @ (SolveError::Timeout | SolveError::OutOfMemory)) => {
+                solver.improved_solution().ok_or(error)
+            }
+            result => result,
+        };
+        let report = Report {
+            source,
+            index,
+            title,
+            result,
+            stats: solver.stats(),
+        };
+        exit_code = exit_code.max(report.exit_code());
+        match options.format {
+            Format::Text => println!("{}", report.to_text()),
+            Format::Json => {
+                let separator = if i + 1 < count { "," } else { "" };
+                println!("  {}{separator}", report.to_json());
+            }
+        }
+    }
+    if options.format == Format::Json {
+        println!("]");
+    }
+    ExitCode::from(exit_code)
+}
+
+/// Parses the command line arguments, returns `None` if help is requested.
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Option<Options>, String> {
+    let mut options = Options::default();
+    while let Some(arg) = args.next() {
+        if !arg.starts_with('-') || arg == "-" {
+            options.files.push(arg);
+            continue;
+        }
+        if arg == "-h" || arg == "--help" {
+            return Ok(None);
+        }
+        if arg == "-a" || arg == "--anytime" {
+            options.solver.anytime = true;
+            continue;
+        }
+        if !VALUE_OPTIONS.contains(&arg.as_str()) {
+            return Err(format!("unknown option '{arg}'"));
+        }
+        let value = args
+            .next()
+            .ok_or_else(|| format!("missing value for '{arg}'"))?;
+        let invalid = || format!("invalid value '{value}' for '{arg}'");
+        match arg.as_str() {
+            "-l" | "--level" => options.levels.push(value),
+            "-s" | "--strategy" => {
+                options.solver.strategy = parse_variant(&value, &STRATEGIES).ok_or_else(invalid)?
+            }
+            "-w" | "--weight" => options.weight = Some(value.parse().map_err(|_| invalid())?),
+            "-b" | "--lower-bound" => {
+                options.solver.lower_bound_method =
+                    parse_variant(&value, &LOWER_BOUND_METHODS).ok_or_else(invalid)?
+            }
+            "-t" | "--timeout" => {
+                let seconds: f64 = value.parse().map_err(|_| invalid())?;
+                options.timeout = Duration::try_from_secs_f64(seconds).map_err(|_| invalid())?;
+            }
+            "-j" | "--threads" => {
+                options.solver.threads = value
+                    .parse()
+                    .ok()
+                    .filter(|&threads| threads > 0)
+                    .ok_or_else(invalid)?
+            }
+            "-m" | "--memory-limit" => {
+                options.solver.memory_limit = value.parse().map_err(|_| invalid())?
+            }
+            "-f" | "--format" => {
+                options.format = match value.as_str() {
+                    "text" => Format::Text,
+                    "json" => Format::Json,
+                    _ => return Err(invalid()),
+                }
+            }
+            _ => unreachable!(),
+        }
+    }
+    if let Some(weight) = options.weight {
+        let Strategy::Weighted {
+            weight: strategy_weight,
+        } = &mut options.solver.strategy
+        else {
+            return Err("'--weight' requires the `Weighted` strategy".to_string());
+        };
+        *strategy_weight = weight;
+    }
+    if options.files.is_empty() {
+        options.files.push("-".to_string());
+    }
+    Ok(Some(options))
+}
+
+/// Finds the variant whose name matches, ignoring case, `-`, `_` and the
+/// fields of the variant.
+///
+/// For example, `OptimalPushMove` matches `optimal-push-move`.
+fn parse_variant<T: Copy + std::fmt::Debug>(name: &str, variants: &[T]) -> Option<T> {
+    let name = name.replace(['-', '_'], "");
+    variants.iter().copied().find(|variant| {
+        format!("{variant:?}")
+            .split(' ')
+            .next()
+            .is_some_and(|variant| variant.eq_ignore_ascii_case(&name))
+    })
+}
@@ -17,6 +17,12 @@ impl Default for MainCamera {
 #[derive(Component)]
 pub struct Hud;
 
+#[derive(Component)]
+pub struct SolverHud;
+
+#[derive(Component)]
+pub struct HintMark;
+
 #[derive(Component)]
 pub struct Board {
     pub board: board::Board,
@@ -7,6 +7,11 @@ use std::{
 use rusqlite::Connection;
 use soukoban::{Actions, Level};
 
+use crate::solve::{
+    optimizer::{Metric, Metrics},
+    pattern_database::DeadlockPattern,
+};
+
 pub struct Database {
     connection: Connection,
 }
@@ -20,7 +25,6 @@ impl Database {
     }
 
     /// Creates a new Database instance with an in-memory connection.
-    #[expect(dead_code)]
     pub fn from_memory() -> Self {
         Self {
             connection: Connection::open_in_memory().expect("failed to open database"),
@@ -51,14 +55,65 @@ impl Database {
                 datetime  DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
                 best_move BOOLEAN NOT NULL DEFAULT 0 CHECK (best_move IN (0, 1)),
                 best_push BOOLEAN NOT NULL DEFAULT 0 CHECK (best_push IN (0, 1)),
+                hinted    BOOLEAN NOT NULL DEFAULT 0 CHECK (hinted IN (0, 1)),
                 PRIMARY KEY (level_id, best_move, best_push),
                 FOREIGN KEY (level_id) REFERENCES tb_level(id) ON DELETE CASCADE
             )
         ";
 
+        const ADD_SNAPSHOT_HINTED_COLUMN: &str = "
+            ALTER TABLE tb_snapshot
+            ADD COLUMN hinted BOOLEAN NOT NULL DEFAULT 0 CHECK (hinted IN (0, 1))
+        ";
+
+        const CREATE_HINT_TABLE: &str = "
+            CREATE TABLE IF NOT EXISTS tb_hint (
+                level_id INTEGER,
+                datetime DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+                FOREIGN KEY (level_id) REFERENCES tb_level(id) ON DELETE CASCADE
+            )
+        ";
+
+        const CREATE_DEADLOCK_PATTERN_TABLE: &str = "
+            CREATE TABLE IF NOT EXISTS tb_deadlock_pattern (
+                level_id INTEGER,
+                pattern  TEXT NOT NULL,
+                PRIMARY KEY (level_id, pattern),
+                FOREIGN KEY (level_id) REFERENCES tb_level(id) ON DELETE CASCADE
+            )
+        ";
+
+        const CREATE_METRIC_SOLUTION_TABLE: &str = "
+            CREATE TABLE IF NOT EXISTS tb_metric_solution (
+                level_id INTEGER,
+                metric   TEXT NOT NULL,
+                actions  TEXT NOT NULL,
+                datetime DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+                PRIMARY KEY (level_id, metric),
+                FOREIGN KEY (level_id) REFERENCES tb_level(id) ON DELETE CASCADE
+            )
+        ";
+
         self.connection.execute(CREATE_LEVEL_TABLE, ()).unwrap();
         self.connection.execute(CREATE_LEVEL_INDICES, ()).unwrap();
         self.connection.execute(CREATE_SNAPSHOT_TABLE, ()).unwrap();
+        // Databases created before hints were added lack the column.
+        if self
+            .connection
+            .prepare("SELECT hinted FROM tb_snapshot LIMIT 0")
+            .is_err()
+        {
+            self.connection
+                .execute(ADD_SNAPSHOT_HINTED_COLUMN, ())
+                .unwrap();
+        }
+        self.connection.execute(CREATE_HINT_TABLE, ()).unwrap();
+        self.connection
+            .execute(CREATE_DEADLOCK_PATTERN_TABLE, ())
+            .unwrap();
+        self.connection
+            .execute(CREATE_METRIC_SOLUTION_TABLE, ())
+            .unwrap();
     }
 
     /// Imports multiple levels into the database.
@@ -172,7 +227,7 @@ impl Database {
             if solution.moves() < best_move_solution.moves() {
                 self.connection
                     .execute(
-                        "UPDATE tb_snapshot SET actions = ? WHERE level_id = ?",
+                        "UPDATE tb_snapshot SET actions = ?, hinted = 0 WHERE level_id = ? AND best_move = 1",
                         (lurd.clone(), level_id as i64),
                     )
                     .unwrap();
@@ -191,7 +246,7 @@ impl Database {
             if solution.pushes() < best_push_solution.pushes() {
                 self.connection
                     .execute(
-                        "UPDATE tb_snapshot SET actions = ? WHERE level_id = ?",
+                        "UPDATE tb_snapshot SET actions = ?, hinted = 0 WHERE level_id = ? AND best_push = 1",
                         (lurd.clone(), level_id as i64),
                     )
                     .unwrap();
@@ -206,6 +261,102 @@ impl Database {
         }
     }
 
+    /// Returns the best solution of the level under the optimization metric.
+    pub fn best_metric_solution(&self, level_id: u64, metric: Metric) -> Option<Actions> {
+        let actions: String = self
+            .connection
+            .query_row(
+                "SELECT actions FROM tb_metric_solution WHERE level_id = ? AND metric = ?",
+                (level_id as i64, format!("{metric:?}")),
+                |row| row.get(0),
+            )
+            .ok()?;
+        Some(Actions::from_str(&actions).unwrap())
+    }
+
+    /// Saves the solution if it is the best solution of the level under the
+    /// optimization metric.
+    ///
+    /// Box lines and box changes are not covered by the best move and best
+    /// push snapshots, so the best solution is kept for each metric.
+    pub fn update_metric_solution(&self, level_id: u64, metric: Metric, solution: &Actions) {
+        let level = self.get_level_by_id(level_id).unwrap();
+        let Some(metrics) = Metrics::new(level.map(), solution) else {
+            return;
+        };
+        if let Some(best_solution) = self.best_metric_solution(level_id, metric)
+            && Metrics::new(level.map(), &best_solution)
+                .is_some_and(|best_metrics| best_metrics.key(metric) <= metrics.key(metric))
+        {
+            return;
+        }
+        self.connection
+            .execute(
+                "INSERT OR REPLACE INTO tb_metric_solution (level_id, metric, actions) VALUES (?, ?, ?)",
+                (level_id as i64, format!("{metric:?}"), solution.to_string()),
+            )
+            .unwrap();
+    }
+
+    /// Records that a hint has been requested for the level.
+    pub fn record_hint(&self, level_id: u64) {
+        self.connection
+            .execute(
+                "INSERT INTO tb_hint (level_id) VALUES (?)",
+                [level_id as i64],
+            )
+            .unwrap();
+    }
+
+    /// Flags the saved solutions of the level which are equal to the solution
+    /// as found with hints.
+    pub fn flag_hinted_solution(&self, level_id: u64, solution: &Actions) {
+        self.connection
+            .execute(
+                "UPDATE tb_snapshot SET hinted = 1 WHERE level_id = ? AND actions = ?",
+                (level_id as i64, solution.to_string()),
+            )
+            .unwrap();
+    }
+
+    /// Checks if the best move solution of the level was found with hints.
+    pub fn is_best_move_solution_hinted(&self, level_id: u64) -> bool {
+        self.connection
+            .query_row(
+                "SELECT hinted FROM tb_snapshot WHERE level_id = ? AND best_move = 1",
+                [level_id as i64],
+                |row| row.get(0),
+            )
+            .unwrap_or(false)
+    }
+
+    /// Returns the deadlock patterns proven by previous searches of the level.
+    pub fn deadlock_patterns(&self, level_id: u64) -> Vec<DeadlockPattern> {
+        let mut statement = self
+            .connection
+            .prepare("SELECT pattern FROM tb_deadlock_pattern WHERE level_id = ?")
+            .unwrap();
+        statement
+            .query_map([level_id as i64], |row| row.get::<_, String>(0))
+            .unwrap()
+            .filter_map(|pattern| pattern.ok()?.parse().ok())
+            .collect()
+    }
+
+    /// Saves the deadlock patterns of the level, existing patterns are kept.
+    pub fn update_deadlock_patterns(&self, level_id: u64, patterns: &[DeadlockPattern]) {
+        self.connection.execute("BEGIN TRANSACTION", []).unwrap();
+        for pattern in patterns {
+            self.connection
+                .execute(
+                    "INSERT OR IGNORE INTO tb_deadlock_pattern (level_id, pattern) VALUES (?, ?)",
+                    (level_id as i64, pattern.to_string()),
+                )
+                .unwrap();
+        }
+        self.connection.execute("COMMIT", []).unwrap();
+    }
+
     /// Returns the maximum level ID.
     pub fn max_level_id(&self) -> Option<u64> {
         self.connection
@@ -27,6 +27,8 @@ pub enum Action {
     ToggleAutomaticSolution,
     ToggleFullscreen,
 
+    Hint,
+
     ImportLevelsFromClipboard,
     ExportLevelToClipboard,
 }
@@ -59,6 +61,7 @@ pub fn default_input_map() -> InputMap<Action> {
             (Action::ToggleInstantMove, KeyCode::KeyI),
             (Action::ToggleAutomaticSolution, KeyCode::KeyP),
             (Action::ToggleFullscreen, KeyCode::F11),
+            (Action::Hint, KeyCode::KeyT),
             // Vim
             (Action::MoveUp, KeyCode::KeyK),
             (Action::MoveDown, KeyCode::KeyJ),
@@ -110,6 +113,7 @@ pub fn default_input_map() -> InputMap<Action> {
         (Action::ZoomOut, GamepadButton::LeftTrigger2),
         (Action::ToggleInstantMove, GamepadButton::West),
         (Action::ToggleAutomaticSolution, GamepadButton::North),
+        (Action::Hint, GamepadButton::Select),
     ]);
     InputMap::default()
         .merge(&mouse_input_map)
@@ -0,0 +1,8 @@
+// #![feature(test)]
+#![allow(clippy::op_ref)]
+
+pub mod board;
+pub mod database;
+pub mod solve;
+mod test;
+pub mod utils;
@@ -1,19 +1,15 @@
-// #![feature(test)]
 #![allow(clippy::op_ref)]
 
-mod board;
 mod components;
-mod database;
 mod events;
 mod input_map;
 mod plugins;
 mod resources;
 mod settings;
-mod solve;
 mod state;
 mod systems;
-mod test;
-mod utils;
+
+use sokoban_rs::{board, database, solve, utils};
 
 use events::*;
 use input_map::*;
@@ -52,6 +48,7 @@ fn main() {
         (
             (
                 mouse_input,
+                save_solution.run_if(on_message::<LevelSolved>),
                 auto_switch_to_next_unsolved_level.run_if(on_message::<LevelSolved>),
                 spawn_board.run_if(resource_changed_or_removed::<LevelId>),
             )
@@ -64,6 +61,11 @@ fn main() {
     .add_systems(
         FixedUpdate,
         (handle_player_movement, smooth_tile_motion).run_if(in_state(AppState::Main)),
+    )
+    .add_systems(Update, update_optimized_solutions)
+    .add_systems(
+        Update,
+        tint_deadlocked_boxes.run_if(resource_changed::<Deadlocks>),
     );
 
     app.add_plugins((
@@ -75,8 +77,12 @@ fn main() {
         camera::plugin,
         auto_move::plugin,
         auto_solve::plugin,
+        hint::plugin,
     ));
 
+    app.init_resource::<SolutionOptimizer>();
+    app.init_resource::<Deadlocks>();
+
     app.init_resource::<ActionState<Action>>()
         .insert_resource(default_input_map())
         .add_message::<ActionDiffMessage<Action>>();
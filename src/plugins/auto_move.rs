@@ -2,12 +2,13 @@
 
 use bevy::{color::palettes::css::*, prelude::*};
 use itertools::Itertools;
-use soukoban::{Tiles, deadlock::compute_static_deadlocks, path_finding::compute_reachable_area};
+use soukoban::{Tiles, path_finding::compute_reachable_area};
 
 use crate::{
     AppState, box_pushable_paths,
     components::{Board, Box, GridPosition, Player},
     resources::AutoMoveState,
+    solve::push_distance::PushDistances,
     systems::input::*,
 };
 
@@ -40,9 +41,9 @@ pub fn spawn_auto_move_marks(
         } => {
             *paths = box_pushable_paths(map, box_position);
 
-            // remove static deadlock positions
-            let static_deadlocks = compute_static_deadlocks(map);
-            paths.retain(|state, _| !static_deadlocks.contains(&state.box_position));
+            // remove dead squares
+            let push_distances = PushDistances::new(map);
+            paths.retain(|state, _| !push_distances.is_dead_square(state.box_position));
 
             // spawn box pushable marks
             for mark_position in paths.keys().map(|state| state.box_position).unique() {
@@ -1,12 +1,14 @@
-use std::time::{Duration, Instant};
-
-use bevy::{color::palettes::css::*, prelude::*};
+use bevy::{color::palettes::css::*, prelude::*, time::Stopwatch};
+use soukoban::Map;
 
 use crate::{
     AppState,
-    components::{Board, Box, GridPosition, Player},
+    components::{Board, Box, GridPosition, Player, SolverHud},
     resources::*,
-    solve::solver::*,
+    solve::{
+        config::SolverConfig, sokoban_solver::SokobanSolver, solver::*, stats::SolverStats,
+        worker::*,
+    },
     systems::input::*,
 };
 
@@ -15,6 +17,7 @@ pub fn plugin(app: &mut App) {
         OnEnter(AppState::AutoSolve),
         (
             (load_solver, spawn_lowerbound_marks).chain(),
+            spawn_solver_hud,
             clear_action_state,
         ),
     );
@@ -22,6 +25,7 @@ pub fn plugin(app: &mut App) {
         Update,
         (
             update_solver,
+            update_solver_hud,
             update_tile_grid_position,
             update_tile_translation,
         )
@@ -40,31 +44,66 @@ pub fn plugin(app: &mut App) {
     app.insert_resource(SolverState::default());
 }
 
-/// Loads the solver state with the current board data and initializes a new solver.
+/// Loads the solver state with the current board data and starts a new solver.
 pub fn load_solver(
     mut solver_state: ResMut<SolverState>,
     board: Query<&Board>,
     config: Res<Config>,
+    database: Res<Database>,
+    level_id: Res<LevelId>,
 ) {
     let board = &board.single().unwrap().board;
-    let SolverState {
-        solver,
-        stopwatch,
-        origin_board,
-    } = &mut *solver_state;
-    *origin_board = board.clone();
-    let solver = solver.get_mut().unwrap();
-    *solver = Solver::new(
-        origin_board.map.clone(),
-        config.solver.strategy,
-        config.solver.lower_bound_method,
-    );
-    stopwatch.reset();
+    let solver = create_solver(board.map.clone(), &config.solver, &database, level_id.0);
+    *solver_state = SolverState {
+        lower_bounds: solver.lower_bounds(),
+        deadlock_patterns: solver.deadlock_patterns(),
+        worker: Some(SolverWorker::spawn(solver)),
+        status: SolverStatus::Running,
+        stopwatch: Stopwatch::new(),
+        origin_board: board.clone(),
+        stats: SolverStats::default(),
+    };
+}
+
+/// Creates the solver selected by the configuration for the map of the level.
+pub fn create_solver(
+    map: Map,
+    config: &SolverConfig,
+    database: &Database,
+    level_id: u64,
+) -> Box<dyn SokobanSolver> {
+    let deadlock_patterns = if config.persist_deadlock_patterns {
+        database.lock().unwrap().deadlock_patterns(level_id)
+    } else {
+        Vec::new()
+    };
+    config.create_solver(map, deadlock_patterns)
 }
 
-/// Unloads the solver state by resetting it to default values.
-pub fn unload_solver(mut solver_state: ResMut<SolverState>) {
-    *solver_state = SolverState::default();
+/// Unloads the solver state, cancelling the search if it is still running.
+pub fn unload_solver(
+    mut solver_state: ResMut<SolverState>,
+    config: Res<Config>,
+    database: Res<Database>,
+    level_id: Res<LevelId>,
+) {
+    if solver_state.status == SolverStatus::Running {
+        solver_state.status = SolverStatus::Cancelled;
+        info!(
+            "Solver: Cancelled ({} sec)",
+            solver_state.stopwatch.elapsed().as_millis() as f32 / 1000.0
+        );
+    }
+    // Dropping the worker cancels the search.
+    solver_state.worker = None;
+
+    if config.solver.persist_deadlock_patterns
+        && let Some(deadlock_patterns) = &solver_state.deadlock_patterns
+    {
+        let deadlock_patterns = deadlock_patterns.read().unwrap();
+        let database = database.lock().unwrap();
+        database.update_deadlock_patterns(level_id.0, deadlock_patterns.patterns());
+    }
 }
 
 /// Spawns lower bound marks on the board based on the solver's lower bounds.
@@ -74,11 +113,12 @@ pub fn spawn_lowerbound_marks(
     mut board: Query<&mut Board>,
 ) {
     let Board { board, tile_size } = &mut *board.single_mut().unwrap();
-    let solver = solver_state.solver.lock().unwrap();
 
-    let lowerbounds = solver.lower_bounds().clone();
-    let max_lowerbound = lowerbounds.values().cloned().max().unwrap();
-    for (position, lowerbound) in lowerbounds {
+    let lowerbounds = &solver_state.lower_bounds;
+    let Some(max_lowerbound) = lowerbounds.values().cloned().max() else {
+        return;
+    };
+    for (position, &lowerbound) in lowerbounds {
         let alpha = lowerbound as f32 / max_lowerbound as f32;
         let color = BLUE * alpha + RED * (1.0 - alpha);
         commands.spawn((
@@ -97,79 +137,215 @@ pub fn spawn_lowerbound_marks(
     }
 }
 
+/// Spawns a text showing the progress of the solver.
+pub fn spawn_solver_hud(mut commands: Commands) {
+    const ALPHA: f32 = 0.8;
+    const FONT_SIZE: f32 = 14.0;
+
+    commands.spawn((
+        Name::new("Solver HUD"),
+        SolverHud,
+        Text::default(),
+        TextFont::from_font_size(FONT_SIZE),
+        TextColor(GOLD.with_alpha(ALPHA).into()),
+        Node {
+            position_type: PositionType::Absolute,
+            top: Val::Px(5.0),
+            left: Val::Px(5.0),
+            ..default()
+        },
+        DespawnOnExit(AppState::AutoSolve),
+    ));
+}
+
+/// Updates the text showing the progress of the solver.
+pub fn update_solver_hud(
+    mut solver_hud: Query<&mut Text, With<SolverHud>>,
+    solver_state: Res<SolverState>,
+) {
+    if !solver_state.is_changed() {
+        return;
+    }
+    let Ok(mut text) = solver_hud.single_mut() else {
+        return;
+    };
+    let stats = &solver_state.stats;
+    let best_lower_bound = stats
+        .best_lower_bound
+        .map_or_else(|| "-".to_string(), |lower_bound| lower_bound.to_string());
+    text.0 = format!(
+        "Expanded   : {}\n\
+         Generated  : {}\n\
+         Duplicates : {}\n\
+         Open       : {}\n\
+         Visited    : {}\n\
+         Memory     : {:.1} MiB\n\
+         Lower bound: {}\n\
+         Nodes/s    : {:.0}\n",
+        stats.expanded_nodes,
+        stats.generated_nodes,
+        stats.duplicates,
+        stats.open_nodes,
+        stats.visited_nodes,
+        stats.memory_usage as f64 / (1024.0 * 1024.0),
+        best_lower_bound,
+        stats.nodes_per_second(),
+    );
+}
+
 /// Resets the board to the state before automatic solution
 pub fn reset_board(mut board: Query<&mut Board>, solver_state: Res<SolverState>) {
     let board = &mut board.single_mut().unwrap().board;
     *board = solver_state.origin_board.clone();
 }
 
+/// Receives messages from the solver worker and updates the board accordingly.
+///
+/// Improved solutions of anytime searches are saved to the database.
 pub fn update_solver(
     mut solver_state: ResMut<SolverState>,
     mut board: Query<&mut Board>,
     mut player_movement: ResMut<PlayerMovement>,
+    time: Res<Time>,
+    database: Res<Database>,
+    level_id: Res<LevelId>,
 
     mut next_state: ResMut<NextState<AppState>>,
 ) {
     let board = &mut board.single_mut().unwrap().board;
     let SolverState {
-        solver,
+        worker,
+        status,
         stopwatch,
         origin_board,
+        stats: solver_stats,
+        ..
     } = &mut *solver_state;
 
-    *board = crate::board::Board::with_map(origin_board.map.clone());
+    if *status != SolverStatus::Running {
+        return;
+    }
+    stopwatch.tick(time.delta());
 
-    let solver = solver.get_mut().unwrap();
-    let timeout = Duration::from_millis(50);
-    let timer = Instant::now();
-    match solver.search(timeout) {
-        Ok(solution) => {
-            let mut verify_board = board.clone();
-            for action in &*solution {
-                verify_board.do_action(action.direction());
+    let Some(worker) = worker else {
+        return;
+    };
+    for message in worker.messages() {
+        match message {
+            SolverMessage::Progress {
+                best_actions,
+                stats,
+            } => {
+                *solver_stats = stats;
+                let Some(best_actions) = best_actions else {
+                    continue;
+                };
+                *board = crate::board::Board::with_map(origin_board.map.clone());
+                for action in &*best_actions {
+                    board.do_action(action.direction());
+                }
             }
-            assert!(verify_board.is_solved());
-
-            stopwatch.tick(timer.elapsed());
-            info!(
-                "Solver: Solved ({} sec)",
-                stopwatch.elapsed().as_millis() as f32 / 1000.0
-            );
-            info!(
-                "    Moves: {}, pushes: {}",
-                solution.moves(),
-                solution.pushes()
-            );
-            info!("    Solution: {}", solution.to_string());
-
-            for action in &*solution {
-                player_move_unchecked(action.direction(), &mut player_movement);
+            SolverMessage::Improved { solution, stats } => {
+                *solver_stats = stats;
+                info!(
+                    "Solver: Improved ({} sec)",
+                    stopwatch.elapsed().as_millis() as f32 / 1000.0
+                );
+                info!(
+                    "    Moves: {}, pushes: {}",
+                    solution.moves(),
+                    solution.pushes()
+                );
+                database
+                    .lock()
+                    .unwrap()
+                    .update_solution(level_id.0, &solution);
+            }
+            SolverMessage::Finished {
+                result: Ok(solution),
+                stats,
+            } => {
+                *solver_stats = stats;
+                *board = crate::board::Board::with_map(origin_board.map.clone());
+                let mut verify_board = board.clone();
+                for action in &*solution {
+                    verify_board.do_action(action.direction());
+                }
+                assert!(verify_board.is_solved());
+
+                *status = SolverStatus::Solved;
+                info!(
+                    "Solver: Solved ({} sec)",
+                    stopwatch.elapsed().as_millis() as f32 / 1000.0
+                );
+                info!(
+                    "    Moves: {}, pushes: {}",
+                    solution.moves(),
+                    solution.pushes()
+                );
+                info!("    Solution: {}", solution.to_string());
+                info!("    Stats: {stats}");
+                info!("    Pruning: {}", stats.pruning);
+
+                for action in &*solution {
+                    player_move_unchecked(action.direction(), &mut player_movement);
+                }
+                next_state.set(AppState::Main);
+                return;
+            }
+            SolverMessage::Finished {
+                result: Err(SolveError::NoSolution),
+                stats,
+            } => {
+                *solver_stats = stats;
+                *status = SolverStatus::NoSolution;
+                info!(
+                    "Solver: No solution ({} sec)",
+                    stopwatch.elapsed().as_millis() as f32 / 1000.0
+                );
+                info!("    Stats: {stats}");
+                info!("    Pruning: {}", stats.pruning);
+                next_state.set(AppState::Main);
+                return;
+            }
+            SolverMessage::Finished {
+                result: Err(SolveError::OutOfMemory),
+                stats,
+            } => {
+                *solver_stats = stats;
+                *status = SolverStatus::OutOfMemory;
+                info!(
+                    "Solver: Out of memory ({} sec)",
+                    stopwatch.elapsed().as_millis() as f32 / 1000.0
+                );
+                info!("    Stats: {stats}");
+                info!("    Pruning: {}", stats.pruning);
+                next_state.set(AppState::Main);
+                return;
+            }
+            SolverMessage::Finished {
+                result: Err(SolveError::Timeout),
+                stats,
+            } => {
+                *solver_stats = stats;
+                *status = SolverStatus::Timeout;
+                info!(
+                    "Solver: Timeout ({} sec)",
+                    stopwatch.elapsed().as_millis() as f32 / 1000.0
+                );
+                info!("    Stats: {stats}");
+                next_state.set(AppState::Main);
+                return;
+            }
+            SolverMessage::Finished {
+                result: Err(SolveError::SpawnFailed),
+                ..
+            } => {
+                *status = SolverStatus::SpawnFailed;
+                info!("Solver: The external solver could not be started");
+                next_state.set(AppState::Main);
+                return;
             }
-            next_state.set(AppState::Main);
-            return;
-        }
-        Err(SolveError::NoSolution) => {
-            stopwatch.tick(timer.elapsed());
-            info!(
-                "Solver: No solution ({} sec)",
-                stopwatch.elapsed().as_millis() as f32 / 1000.0
-            );
-            next_state.set(AppState::Main);
-            return;
-        }
-        Err(SolveError::Timeout) => {
-            stopwatch.tick(timer.elapsed());
-        }
-    }
-    if let Some(best_state) = solver.best_state() {
-        // println!(
-        //     "lower bound: {:3}, moves: {:3}, pushes: {:3}",
-        //     best_state.lower_bound(&solver),
-        //     best_state.actions.moves(),
-        //     best_state.actions.pushes()
-        // );
-        for action in &*best_state.actions {
-            board.do_action(action.direction());
         }
     }
 }
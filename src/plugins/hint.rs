@@ -0,0 +1,223 @@
+#![allow(clippy::too_many_arguments)]
+
+use std::time::Duration;
+
+use bevy::{color::palettes::css::*, prelude::*, time::Stopwatch};
+use leafwing_input_manager::prelude::*;
+use nalgebra::Vector2;
+use soukoban::Actions;
+
+use crate::{
+    Action, AppState,
+    components::{Board, HintMark},
+    plugins::auto_solve::create_solver,
+    resources::*,
+    solve::{solver::*, worker::*},
+};
+
+/// Time limit of the hint search.
+const HINT_TIME_LIMIT: Duration = Duration::from_secs(10);
+
+pub fn plugin(app: &mut App) {
+    app.add_systems(
+        Update,
+        (
+            reset_hint.run_if(resource_changed::<LevelId>),
+            request_hint.run_if(in_state(AppState::Main)),
+            update_hint,
+        )
+            .chain(),
+    );
+    app.add_systems(OnEnter(AppState::AutoSolve), cancel_hint);
+    app.insert_resource(HintState::default());
+}
+
+/// Starts searching for a hint from the current board.
+pub fn request_hint(
+    action_state: Res<ActionState<Action>>,
+    mut commands: Commands,
+    mut hint_state: ResMut<HintState>,
+    marks: Query<Entity, With<HintMark>>,
+    board: Query<&Board>,
+    config: Res<Config>,
+    database: Res<Database>,
+    level_id: Res<LevelId>,
+) {
+    if !action_state.just_pressed(&Action::Hint) {
+        return;
+    }
+    let board = &board.single().unwrap().board;
+    if board.is_solved() {
+        return;
+    }
+    despawn_hint_marks(&mut commands, &marks);
+
+    database.lock().unwrap().record_hint(level_id.0);
+    let solver = create_solver(board.map.clone(), &config.solver, &database, level_id.0);
+    *hint_state = HintState {
+        worker: Some(SolverWorker::spawn(solver)),
+        stopwatch: Stopwatch::new(),
+        board: Some(board.clone()),
+        solution: None,
+        used: true,
+    };
+    info!("Hint: Searching");
+}
+
+/// Receives the result of the hint search, and discards the hint once the
+/// board has changed.
+pub fn update_hint(
+    mut commands: Commands,
+    mut hint_state: ResMut<HintState>,
+    marks: Query<Entity, With<HintMark>>,
+    board: Query<&Board>,
+    time: Res<Time>,
+) {
+    let Board { board, tile_size } = board.single().unwrap();
+    let HintState {
+        worker,
+        stopwatch,
+        board: hint_board,
+        solution: best_solution,
+        ..
+    } = &mut *hint_state;
+    let Some(origin_board) = hint_board else {
+        return;
+    };
+
+    if board.map.player_position() != origin_board.map.player_position()
+        || board.map.box_positions() != origin_board.map.box_positions()
+    {
+        // Dropping the worker cancels the search.
+        *worker = None;
+        *hint_board = None;
+        *best_solution = None;
+        despawn_hint_marks(&mut commands, &marks);
+        return;
+    }
+
+    let Some(solver_worker) = worker else {
+        return;
+    };
+    stopwatch.tick(time.delta());
+    // Drained first, so that a result received in the frame the time runs out is kept.
+    for message in solver_worker.messages() {
+        let result = match message {
+            SolverMessage::Improved { solution, .. } => {
+                *best_solution = Some(solution);
+                continue;
+            }
+            SolverMessage::Finished { result, .. } => result,
+            SolverMessage::Progress { .. } => continue,
+        };
+        match result {
+            Ok(solution) => {
+                info!(
+                    "Hint: Found ({} sec)",
+                    stopwatch.elapsed().as_millis() as f32 / 1000.0
+                );
+                spawn_hint_marks(&mut commands, origin_board, *tile_size, &solution);
+            }
+            Err(SolveError::NoSolution) => info!("Hint: The current position is unsolvable"),
+            Err(SolveError::OutOfMemory) => info!("Hint: Out of memory"),
+            Err(SolveError::Timeout) => info!("Hint: Not found"),
+            Err(SolveError::SpawnFailed) => info!("Hint: The solver could not be started"),
+        }
+        *worker = None;
+        return;
+    }
+    if stopwatch.elapsed() >= HINT_TIME_LIMIT {
+        // Anytime searches may still be improving their solution, the best one
+        // found so far is good enough for a hint.
+        if let Some(solution) = best_solution.take() {
+            info!(
+                "Hint: Found ({} sec)",
+                stopwatch.elapsed().as_millis() as f32 / 1000.0
+            );
+            spawn_hint_marks(&mut commands, origin_board, *tile_size, &solution);
+        } else {
+            info!(
+                "Hint: Not found ({} sec)",
+                stopwatch.elapsed().as_millis() as f32 / 1000.0
+            );
+        }
+        *worker = None;
+    }
+}
+
+/// Resets the hint when the level is loaded or reset.
+pub fn reset_hint(
+    mut commands: Commands,
+    mut hint_state: ResMut<HintState>,
+    marks: Query<Entity, With<HintMark>>,
+) {
+    *hint_state = HintState::default();
+    despawn_hint_marks(&mut commands, &marks);
+}
+
+/// Cancels the hint search, the board is replaced by the automatic solution.
+pub fn cancel_hint(
+    mut commands: Commands,
+    mut hint_state: ResMut<HintState>,
+    marks: Query<Entity, With<HintMark>>,
+) {
+    hint_state.worker = None;
+    hint_state.board = None;
+    despawn_hint_marks(&mut commands, &marks);
+}
+
+/// Spawns marks for the walking path of the player, the box and the
+/// destination of the first push of the solution.
+fn spawn_hint_marks(
+    commands: &mut Commands,
+    board: &crate::board::Board,
+    tile_size: Vector2<i32>,
+    solution: &Actions,
+) {
+    const MARK_COLOR: Srgba = LIME;
+    const HIGHLIGHT_COLOR: Srgba = TURQUOISE;
+
+    let Some(push_index) = solution.iter().position(|action| action.is_push()) else {
+        return;
+    };
+    let translation = |position: Vector2<i32>, z: f32| {
+        Transform::from_xyz(
+            (position.x * tile_size.x) as f32,
+            ((board.map.dimensions().y - position.y) * tile_size.y) as f32,
+            z,
+        )
+    };
+    let tile_size = Vec2::new(tile_size.x as f32, tile_size.y as f32);
+
+    let mut player_position = board.map.player_position();
+    for action in solution.iter().take(push_index) {
+        player_position = player_position + &action.direction().into();
+        commands.spawn((
+            Name::new("Hint path mark"),
+            HintMark,
+            Sprite::from_color(MARK_COLOR.with_alpha(0.8), tile_size / 4.0),
+            translation(player_position, 10.0),
+        ));
+    }
+
+    let direction = solution[push_index].direction();
+    let box_position = player_position + &direction.into();
+    commands.spawn((
+        Name::new("Hint box mark"),
+        HintMark,
+        Sprite::from_color(HIGHLIGHT_COLOR.with_alpha(0.5), tile_size),
+        translation(box_position, 10.0),
+    ));
+    commands.spawn((
+        Name::new("Hint destination mark"),
+        HintMark,
+        Sprite::from_color(HIGHLIGHT_COLOR.with_alpha(0.8), tile_size / 2.0),
+        translation(box_position + &direction.into(), 10.0),
+    ));
+}
+
+fn despawn_hint_marks(commands: &mut Commands, marks: &Query<Entity, With<HintMark>>) {
+    for mark in marks {
+        commands.entity(mark).despawn();
+    }
+}
@@ -6,4 +6,5 @@ pub mod auto_move;
 pub mod auto_solve;
 pub mod camera;
 pub mod config;
+pub mod hint;
 pub mod ui;
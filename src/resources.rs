@@ -1,13 +1,24 @@
 use bevy::{prelude::*, time::Stopwatch};
 use nalgebra::Vector2;
 use serde::{Deserialize, Serialize};
-use soukoban::{Map, direction::Direction};
+use soukoban::{Actions, Map, direction::Direction};
 
-use crate::{board::Board, database, solve::solver::*, utils::PushState};
+use crate::{
+    board::Board,
+    database,
+    solve::{
+        config::SolverConfig,
+        pattern_database::PatternDatabase,
+        solver::*,
+        stats::SolverStats,
+        worker::{OptimizerWorker, SolverWorker},
+    },
+    utils::PushState,
+};
 
 use std::{
-    collections::{HashMap, VecDeque},
-    sync::Mutex,
+    collections::{HashMap, HashSet, VecDeque},
+    sync::{Arc, Mutex, RwLock},
 };
 
 #[derive(Resource, Serialize, Deserialize)]
@@ -22,6 +33,12 @@ pub struct Config {
     pub instant_move: bool,
     /// Enable auto switch to next unsolved level when the current level is solved.
     pub auto_switch_to_next_unsolved_level: bool,
+    /// Prevent pushes which lead to a deadlock.
+    #[serde(default)]
+    pub prevent_deadlock_pushes: bool,
+    /// Also detect corral deadlocks during play, which is slower.
+    #[serde(default)]
+    pub detect_corral_deadlocks: bool,
     pub solver: SolverConfig,
 }
 
@@ -33,23 +50,40 @@ impl Default for Config {
             volume: 0.5,
             instant_move: false,
             auto_switch_to_next_unsolved_level: true,
+            prevent_deadlock_pushes: false,
+            detect_corral_deadlocks: false,
             solver: SolverConfig::default(),
         }
     }
 }
 
-#[derive(Serialize, Deserialize, Default)]
-pub struct SolverConfig {
-    pub strategy: Strategy,
-    pub lower_bound_method: LowerBoundMethod,
-}
-
 #[derive(Resource, Deref)]
 pub struct Database(pub Mutex<database::Database>);
 
 #[derive(Resource, Deref, DerefMut)]
 pub struct LevelId(pub u64);
 
+/// Solutions being optimized in the background, with the IDs of their levels
+/// and whether they were found with hints.
+#[derive(Resource, Default)]
+pub struct SolutionOptimizer {
+    pub workers: Vec<(u64, bool, OptimizerWorker)>,
+}
+
+/// Hint requested by the player.
+#[derive(Resource, Default)]
+pub struct HintState {
+    pub worker: Option<SolverWorker>,
+    /// Time elapsed since the hint was requested.
+    pub stopwatch: Stopwatch,
+    /// Board the hint is searched for.
+    pub board: Option<Board>,
+    /// Best solution reported so far by an anytime search.
+    pub solution: Option<Actions>,
+    /// Whether hints have been requested since the level was loaded.
+    pub used: bool,
+}
+
 #[derive(Resource)]
 pub struct PlayerMovement {
     pub directions: VecDeque<Direction>,
@@ -65,6 +99,73 @@ impl PlayerMovement {
     }
 }
 
+/// Deadlocks detected on the board during play.
+#[derive(Resource, Default)]
+pub struct Deadlocks {
+    /// Solver of the current level, only used to detect deadlocks.
+    detector: Option<Solver>,
+    /// Box positions the deadlocks were detected for.
+    box_positions: HashSet<Vector2<i32>>,
+    /// Boxes which are part of a deadlock.
+    pub boxes: HashSet<Vector2<i32>>,
+}
+
+impl Deadlocks {
+    /// Detects the deadlocks of the board if the boxes have moved.
+    ///
+    /// The resource is only marked as changed if the deadlocked boxes have
+    /// changed.
+    pub fn update(
+        deadlocks: &mut impl DetectChangesMut<Inner = Self>,
+        board: &Board,
+        corral: bool,
+    ) {
+        let this = deadlocks.bypass_change_detection();
+        if *board.map.box_positions() == this.box_positions {
+            return;
+        }
+        this.box_positions = board.map.box_positions().clone();
+        let boxes = this.detect(board, corral);
+        if boxes != this.boxes {
+            this.boxes = boxes;
+            deadlocks.set_changed();
+        }
+    }
+
+    /// Checks if moving in the direction pushes a box into a new deadlock.
+    pub fn is_deadlock_push(
+        deadlocks: &mut impl DetectChangesMut<Inner = Self>,
+        board: &Board,
+        direction: Direction,
+        corral: bool,
+    ) -> bool {
+        let box_position = board.map.player_position() + &direction.into();
+        if !board.map.box_positions().contains(&box_position) || !board.moveable(direction) {
+            return false;
+        }
+        Self::update(deadlocks, board, corral);
+        let mut next_board = board.clone();
+        next_board.do_action(direction);
+        let this = deadlocks.bypass_change_detection();
+        !this.detect(&next_board, corral).is_subset(&this.boxes)
+    }
+
+    fn detect(&mut self, board: &Board, corral: bool) -> HashSet<Vector2<i32>> {
+        let detector = self.detector.get_or_insert_with(|| {
+            Solver::new(
+                board.map.clone(),
+                Strategy::Fast,
+                LowerBoundMethod::MinimumPush,
+            )
+        });
+        detector.deadlocked_boxes(
+            board.map.player_position(),
+            board.map.box_positions(),
+            corral,
+        )
+    }
+}
+
 #[derive(Resource, Default)]
 pub enum AutoMoveState {
     #[default]
@@ -75,23 +176,43 @@ pub enum AutoMoveState {
     },
 }
 
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum SolverStatus {
+    #[default]
+    Idle,
+    Running,
+    Solved,
+    NoSolution,
+    OutOfMemory,
+    Timeout,
+    SpawnFailed,
+    Cancelled,
+}
+
 #[derive(Resource)]
 pub struct SolverState {
-    pub solver: Mutex<Solver>,
+    pub worker: Option<SolverWorker>,
+    pub status: SolverStatus,
+    /// Time elapsed since the solver was started.
     pub stopwatch: Stopwatch,
     pub origin_board: Board,
+    pub lower_bounds: HashMap<Vector2<i32>, usize>,
+    /// Deadlock patterns shared with the running solver, if it proves any.
+    pub deadlock_patterns: Option<Arc<RwLock<PatternDatabase>>>,
+    /// Latest statistics reported by the solver.
+    pub stats: SolverStats,
 }
 
 impl Default for SolverState {
     fn default() -> Self {
         Self {
-            solver: Mutex::new(Solver::new(
-                Map::with_dimensions(Vector2::new(0, 0)),
-                Strategy::default(),
-                LowerBoundMethod::default(),
-            )),
+            worker: None,
+            status: SolverStatus::default(),
             stopwatch: Stopwatch::new(),
             origin_board: Board::with_map(Map::with_dimensions(Vector2::new(0, 0))),
+            lower_bounds: HashMap::new(),
+            deadlock_patterns: None,
+            stats: SolverStats::default(),
         }
     }
 }
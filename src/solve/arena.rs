@@ -0,0 +1,92 @@
+use nalgebra::Vector2;
+use soukoban::direction::Direction;
+
+/// Identifies a node stored in an arena.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct NodeId {
+    arena: u32,
+    index: u32,
+}
+
+impl NodeId {
+    /// Returns the index of the arena the node is stored in.
+    pub fn arena(self) -> usize {
+        self.arena as usize
+    }
+}
+
+/// A push of a single box, repeated `count` times in the same direction.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Push {
+    /// Position of the box before the push.
+    pub box_position: Vector2<i32>,
+    pub direction: Direction,
+    pub count: u16,
+    /// Index in the packing order of the goal macro following the push, which
+    /// moves the box from the entrance of the goal room to its goal.
+    pub goal_macro: Option<u16>,
+}
+
+impl Push {
+    /// Returns the position of the box after the straight pushes, excluding
+    /// the goal macro.
+    pub fn destination(&self) -> Vector2<i32> {
+        let offset: Vector2<i32> = self.direction.into();
+        self.box_position + offset * self.count as i32
+    }
+}
+
+/// An expanded state of the search graph.
+///
+/// Only the push leading to the state is stored, the actions of a state are
+/// reconstructed by following the parent pointers back to the initial state.
+#[derive(Clone, Copy, Debug)]
+pub struct Node {
+    pub parent: Option<NodeId>,
+    /// The push which leads from the parent to this node, `None` for the initial state.
+    pub push: Option<Push>,
+}
+
+/// Storage of nodes, which are only removed from the end.
+pub struct Arena {
+    index: u32,
+    nodes: Vec<Node>,
+}
+
+impl Arena {
+    /// Creates a new empty arena. The index must be unique among the arenas of a solver.
+    pub fn new(index: usize) -> Self {
+        Self {
+            index: index as u32,
+            nodes: Vec::new(),
+        }
+    }
+
+    /// Inserts a node and returns its ID.
+    pub fn insert(&mut self, node: Node) -> NodeId {
+        let id = NodeId {
+            arena: self.index,
+            index: self.nodes.len() as u32,
+        };
+        self.nodes.push(node);
+        id
+    }
+
+    /// Removes the nodes inserted after the first `len` nodes.
+    ///
+    /// Used by the depth first search, whose nodes form a single path.
+    pub fn truncate(&mut self, len: usize) {
+        self.nodes.truncate(len);
+    }
+
+    /// Returns the approximate memory used by the nodes, in bytes.
+    pub fn memory_usage(&self) -> usize {
+        self.nodes.capacity() * size_of::<Node>()
+    }
+
+    /// Returns the node with the ID.
+    pub fn get(&self, id: NodeId) -> &Node {
+        debug_assert_eq!(id.arena, self.index);
+        &self.nodes[id.index as usize]
+    }
+}
@@ -0,0 +1,341 @@
+use std::{
+    collections::HashMap,
+    fmt, fs, io,
+    path::Path,
+    time::{Duration, Instant},
+};
+
+use serde::{Serialize, Serializer};
+use soukoban::Level;
+
+use crate::solve::{optimizer::Metrics, solver::*};
+
+/// Runs the solver over level collections.
+#[derive(Clone, Copy, Debug)]
+pub struct Benchmark {
+    pub strategy: Strategy,
+    pub lower_bound_method: LowerBoundMethod,
+    /// Time limit of each level.
+    pub time_limit: Duration,
+}
+
+impl Benchmark {
+    /// Solves every level of the collection files in the directory, in the
+    /// order of their file names.
+    pub fn run(&self, directory: &Path) -> io::Result<BenchmarkReport> {
+        let mut paths: Vec<_> = fs::read_dir(directory)?
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect::<io::Result<_>>()?;
+        paths.retain(|path| {
+            path.extension()
+                .is_some_and(|extension| extension == "xsb" || extension == "sok")
+        });
+        paths.sort();
+
+        let mut report = BenchmarkReport::default();
+        for path in paths {
+            let levels = Level::load_from_str(&fs::read_to_string(&path)?)
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error.to_string()))?;
+            let collection = path.file_name().unwrap().to_string_lossy();
+            report
+                .results
+                .extend(self.run_collection(&collection, &levels));
+        }
+        Ok(report)
+    }
+
+    /// Solves every level of the collection.
+    pub fn run_collection(&self, collection: &str, levels: &[Level]) -> Vec<LevelResult> {
+        levels
+            .iter()
+            .enumerate()
+            .map(|(index, level)| {
+                let mut solver =
+                    Solver::new(level.map().clone(), self.strategy, self.lower_bound_method);
+                let timer = Instant::now();
+                let result = solver.search(self.time_limit);
+                let elapsed = timer.elapsed();
+                let metrics = result.as_ref().ok().map(|solution| {
+                    Metrics::new(level.map(), solution).unwrap_or_else(|| {
+                        panic!("invalid solution of {collection} #{}", index + 1)
+                    })
+                });
+                LevelResult {
+                    collection: collection.to_string(),
+                    index: index + 1,
+                    error: result.err(),
+                    moves: metrics.map(|metrics| metrics.moves),
+                    pushes: metrics.map(|metrics| metrics.pushes),
+                    expanded_nodes: solver.stats().expanded_nodes,
+                    elapsed: Duration::from_micros(elapsed.as_micros() as u64),
+                }
+            })
+            .collect()
+    }
+}
+
+/// Measurements of solving a level.
+#[derive(Clone, PartialEq, Eq, Debug, Serialize)]
+pub struct LevelResult {
+    /// File name of the collection.
+    pub collection: String,
+    /// Index of the level in the collection, starting from 1.
+    pub index: usize,
+    /// Reason why the level is not solved, `None` if it is solved.
+    #[serde(rename = "status", serialize_with = "serialize_status")]
+    pub error: Option<SolveError>,
+    pub moves: Option<usize>,
+    pub pushes: Option<usize>,
+    pub expanded_nodes: usize,
+    #[serde(rename = "elapsed_ms", serialize_with = "serialize_millis")]
+    pub elapsed: Duration,
+}
+
+impl LevelResult {
+    /// Returns `true` if the level is solved.
+    pub fn is_solved(&self) -> bool {
+        self.error.is_none()
+    }
+
+    fn status(&self) -> &'static str {
+        status(&self.error)
+    }
+}
+
+fn status(error: &Option<SolveError>) -> &'static str {
+    match error {
+        None => "solved",
+        Some(SolveError::Timeout) => "timeout",
+        Some(SolveError::NoSolution) => "no_solution",
+        Some(SolveError::OutOfMemory) => "out_of_memory",
+        Some(SolveError::SpawnFailed) => "spawn_failed",
+    }
+}
+
+fn serialize_status<S: Serializer>(
+    error: &Option<SolveError>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(status(error))
+}
+
+fn serialize_millis<S: Serializer>(elapsed: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_f64(elapsed.as_secs_f64() * 1000.0)
+}
+
+/// Results of a benchmark run.
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+pub struct BenchmarkReport {
+    pub results: Vec<LevelResult>,
+}
+
+const CSV_HEADER: &str = "collection,index,status,moves,pushes,expanded_nodes,elapsed_ms";
+
+impl BenchmarkReport {
+    /// Returns the report as CSV, with one row per level.
+    pub fn to_csv(&self) -> String {
+        let mut csv = format!("{CSV_HEADER}\n");
+        for result in &self.results {
+            csv += &format!(
+                "{},{},{},{},{},{},{:.3}\n",
+                result.collection,
+                result.index,
+                result.status(),
+                result
+                    .moves
+                    .map_or(String::new(), |moves| moves.to_string()),
+                result
+                    .pushes
+                    .map_or(String::new(), |pushes| pushes.to_string()),
+                result.expanded_nodes,
+                result.elapsed.as_secs_f64() * 1000.0
+            );
+        }
+        csv
+    }
+
+    /// Parses a report written by [`BenchmarkReport::to_csv`].
+    pub fn from_csv(csv: &str) -> Result<Self, String> {
+        let mut lines = csv.lines().enumerate();
+        if lines.next().map(|(_, header)| header) != Some(CSV_HEADER) {
+            return Err("invalid header".to_string());
+        }
+        let mut results = Vec::new();
+        for (number, line) in lines.filter(|(_, line)| !line.is_empty()) {
+            let invalid = || format!("invalid row at line {}", number + 1);
+            let fields: Vec<_> = line.split(',').collect();
+            let [
+                collection,
+                index,
+                status,
+                moves,
+                pushes,
+                expanded_nodes,
+                elapsed,
+            ] = fields[..]
+            else {
+                return Err(invalid());
+            };
+            let optional = |field: &str| {
+                (!field.is_empty())
+                    .then(|| field.parse().map_err(|_| invalid()))
+                    .transpose()
+            };
+            let elapsed: f64 = elapsed.parse().map_err(|_| invalid())?;
+            results.push(LevelResult {
+                collection: collection.to_string(),
+                index: index.parse().map_err(|_| invalid())?,
+                error: match status {
+                    "solved" => None,
+                    "timeout" => Some(SolveError::Timeout),
+                    "no_solution" => Some(SolveError::NoSolution),
+                    "out_of_memory" => Some(SolveError::OutOfMemory),
+                    "spawn_failed" => Some(SolveError::SpawnFailed),
+                    _ => return Err(invalid()),
+                },
+                moves: optional(moves)?,
+                pushes: optional(pushes)?,
+                expanded_nodes: expanded_nodes.parse().map_err(|_| invalid())?,
+                elapsed: Duration::try_from_secs_f64(elapsed / 1000.0).map_err(|_| invalid())?,
+            });
+        }
+        Ok(Self { results })
+    }
+
+    /// Returns the report as a JSON array, with one object per level.
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(&self.results).unwrap() + "\n"
+    }
+
+    /// Compares the report with a baseline.
+    ///
+    /// Levels solved by the baseline which are no longer solved, solutions
+    /// with more moves or pushes, and levels solved more than
+    /// `max_slowdown_percent` slower are reported. Levels solved by the
+    /// baseline in less than `min_elapsed` are not checked for slowdowns,
+    /// since their timings are mostly noise.
+    pub fn compare(
+        &self,
+        baseline: &Self,
+        max_slowdown_percent: u32,
+        min_elapsed: Duration,
+    ) -> Vec<Regression> {
+        let baseline: HashMap<_, _> = baseline
+            .results
+            .iter()
+            .map(|result| ((result.collection.as_str(), result.index), result))
+            .collect();
+        let mut regressions = Vec::new();
+        for result in &self.results {
+            let Some(baseline) = baseline.get(&(result.collection.as_str(), result.index)) else {
+                continue;
+            };
+            if !baseline.is_solved() {
+                continue;
+            }
+            if !result.is_solved() {
+                regressions.push(Regression::Failed {
+                    baseline: (*baseline).clone(),
+                    result: result.clone(),
+                });
+                continue;
+            }
+            if result.moves > baseline.moves || result.pushes > baseline.pushes {
+                regressions.push(Regression::WorseSolution {
+                    baseline: (*baseline).clone(),
+                    result: result.clone(),
+                });
+            }
+            if baseline.elapsed >= min_elapsed
+                && result.elapsed.as_secs_f64() * 100.0
+                    > baseline.elapsed.as_secs_f64() * (100 + max_slowdown_percent) as f64
+            {
+                regressions.push(Regression::Slower {
+                    baseline: (*baseline).clone(),
+                    result: result.clone(),
+                });
+            }
+        }
+        regressions
+    }
+}
+
+impl fmt::Display for BenchmarkReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut collections: Vec<&str> = Vec::new();
+        for result in &self.results {
+            if !collections.contains(&result.collection.as_str()) {
+                collections.push(&result.collection);
+            }
+        }
+        for collection in collections {
+            let results: Vec<_> = self
+                .results
+                .iter()
+                .filter(|result| result.collection == collection)
+                .collect();
+            let solved = results.iter().filter(|result| result.is_solved()).count();
+            let elapsed: Duration = results.iter().map(|result| result.elapsed).sum();
+            writeln!(
+                f,
+                "{collection}: {solved}/{} solved, {:.3} sec",
+                results.len(),
+                elapsed.as_secs_f64()
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// A level which got worse compared to the baseline.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum Regression {
+    /// The level is no longer solved.
+    Failed {
+        baseline: LevelResult,
+        result: LevelResult,
+    },
+    /// The solution has more moves or pushes.
+    WorseSolution {
+        baseline: LevelResult,
+        result: LevelResult,
+    },
+    /// The level is solved slower.
+    Slower {
+        baseline: LevelResult,
+        result: LevelResult,
+    },
+}
+
+impl fmt::Display for Regression {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Regression::Failed { result, .. } => write!(
+                f,
+                "{} #{}: no longer solved ({})",
+                result.collection,
+                result.index,
+                result.status()
+            ),
+            Regression::WorseSolution { baseline, result } => write!(
+                f,
+                "{} #{}: {} moves, {} pushes (baseline: {} moves, {} pushes)",
+                result.collection,
+                result.index,
+                result.moves.unwrap(),
+                result.pushes.unwrap(),
+                baseline.moves.unwrap(),
+                baseline.pushes.unwrap()
+            ),
+            Regression::Slower { baseline, result } => write!(
+                f,
+                "{} #{}: {:.3} sec (baseline: {:.3} sec)",
+                result.collection,
+                result.index,
+                result.elapsed.as_secs_f64(),
+                baseline.elapsed.as_secs_f64()
+            ),
+        }
+    }
+}
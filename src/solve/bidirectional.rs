@@ -0,0 +1,258 @@
+use std::{
+    cmp::Ordering,
+    collections::{BinaryHeap, HashMap, HashSet},
+};
+
+use nalgebra::Vector2;
+use soukoban::{
+    Tiles,
+    direction::Direction,
+    path_finding::{compute_area_anchor, compute_reachable_area},
+};
+
+use crate::solve::{arena::*, encoding::*, solver::*};
+
+/// The search which first reached a state, and the node leading back to its root.
+#[derive(Clone, Copy)]
+pub enum Visit {
+    /// Reached by pushes from the initial state.
+    Forward(Node),
+    /// Reached by pulls from a solved state.
+    Backward(Node),
+}
+
+/// State of a bidirectional search, kept between calls.
+pub struct BidirectionalSearch {
+    /// Open list of the backward search.
+    pub heap: BinaryHeap<BackwardState>,
+    /// Expanded nodes of the backward search.
+    pub arena: Arena,
+    /// Normalized keys of the states reached by both searches.
+    pub table: HashMap<StateKey, Visit>,
+    /// Manhattan distance of each floor square to the nearest initial box.
+    start_distances: Vec<usize>,
+}
+
+impl BidirectionalSearch {
+    /// Creates a new search whose backward search starts from every player
+    /// area of the solved state.
+    pub fn new(solver: &Solver) -> Self {
+        let start_distances = (0..solver.floor().count())
+            .map(|index| {
+                let position = solver.floor().position(index);
+                solver
+                    .map
+                    .box_positions()
+                    .iter()
+                    .map(|box_position| {
+                        ((box_position.x - position.x).abs() + (box_position.y - position.y).abs())
+                            as usize
+                    })
+                    .min()
+                    .unwrap_or(0)
+            })
+            .collect();
+        let mut instance = Self {
+            heap: BinaryHeap::new(),
+            arena: Arena::new(1),
+            table: HashMap::new(),
+            start_distances,
+        };
+        if solver.map.goal_positions().len() != solver.map.box_positions().len() {
+            return instance;
+        }
+
+        let boxes = BoxSet::from_positions(solver.floor(), solver.map.goal_positions());
+        let boxes_hash = solver.zobrist().boxes_hash(&boxes);
+        let mut visited = HashSet::new();
+        for index in 0..solver.floor().count() {
+            let position = solver.floor().position(index);
+            if boxes.contains(index) || visited.contains(&position) {
+                continue;
+            }
+            let area = compute_reachable_area(position, |position| {
+                !solver.map[position].intersects(Tiles::Wall)
+                    && solver
+                        .floor()
+                        .index(position)
+                        .is_none_or(|index| !boxes.contains(index))
+            });
+            visited.extend(area.iter().copied());
+            let state = BackwardState {
+                player_position: compute_area_anchor(&area).unwrap(),
+                boxes: boxes.clone(),
+                boxes_hash,
+                pulls: 0,
+                parent: None,
+                push: None,
+                heuristic: 0,
+            }
+            .with_heuristic(&instance.start_distances);
+            instance
+                .table
+                .insert(state.normalized_key(solver), Visit::Backward(state.node()));
+            instance.heap.push(state);
+        }
+        instance
+    }
+
+    /// Expands the most promising state of the backward search.
+    ///
+    /// Returns the forward and backward nodes of a state reached by both searches.
+    pub fn step(&mut self, solver: &Solver) -> Option<(Node, Node)> {
+        let state = self.heap.pop()?;
+        let node = self.arena.insert(state.node());
+        for predecessor in state.predecessors(node, solver, &self.start_distances) {
+            let key = predecessor.normalized_key(solver);
+            match self.table.get(&key) {
+                Some(Visit::Forward(forward)) => return Some((*forward, predecessor.node())),
+                Some(Visit::Backward(_)) => solver.record_duplicate(),
+                None => {
+                    self.table.insert(key, Visit::Backward(predecessor.node()));
+                    self.heap.push(predecessor);
+                }
+            }
+        }
+        None
+    }
+
+    /// Returns the approximate memory used by the backward search, in bytes.
+    pub fn memory_usage(&self, solver: &Solver) -> usize {
+        self.heap.len() * solver.state_size()
+            + self.table.len() * solver.key_size()
+            + self.arena.memory_usage()
+    }
+}
+
+/// A state of the backward search, reached from a solved state by pulling boxes.
+#[derive(Clone, Eq)]
+pub struct BackwardState {
+    player_position: Vector2<i32>,
+    boxes: BoxSet,
+    boxes_hash: u64,
+    pulls: usize,
+    /// The expanded node this state was generated from.
+    parent: Option<NodeId>,
+    /// The push which undoes the pull leading to this state.
+    push: Option<Push>,
+    heuristic: usize,
+}
+
+impl PartialEq for BackwardState {
+    fn eq(&self, other: &Self) -> bool {
+        self.player_position == other.player_position && self.boxes == other.boxes
+    }
+}
+
+impl Ord for BackwardState {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.heuristic.cmp(&other.heuristic).reverse()
+    }
+}
+
+impl PartialOrd for BackwardState {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl BackwardState {
+    /// Calculates the heuristic value of the state from the distances of the
+    /// boxes to the initial boxes.
+    fn with_heuristic(mut self, start_distances: &[usize]) -> Self {
+        let distance: usize = self.boxes.iter().map(|index| start_distances[index]).sum();
+        self.heuristic = distance * 10_000 + self.pulls;
+        self
+    }
+
+    /// Returns the node to be stored in an arena when the state is expanded.
+    ///
+    /// Following the parents of the node gives the pushes from this state
+    /// to the solved state in order.
+    pub fn node(&self) -> Node {
+        Node {
+            parent: self.parent,
+            push: self.push,
+        }
+    }
+
+    /// Returns the states the player can reach by pulling a box.
+    fn predecessors(
+        &self,
+        node: NodeId,
+        solver: &Solver,
+        start_distances: &[usize],
+    ) -> Vec<BackwardState> {
+        let has_box_at = |position: Vector2<i32>| {
+            solver
+                .floor()
+                .index(position)
+                .is_some_and(|index| self.boxes.contains(index))
+        };
+        let player_reachable_area = compute_reachable_area(self.player_position, |position| {
+            !solver.map[position].intersects(Tiles::Wall) && !has_box_at(position)
+        });
+
+        let mut predecessors = Vec::new();
+        for box_index in self.boxes.iter() {
+            let box_position = solver.floor().position(box_index);
+            for pull_direction in Direction::iter() {
+                let new_box_position = box_position + &pull_direction.into();
+                let new_player_position = new_box_position + &pull_direction.into();
+                if !player_reachable_area.contains(&new_box_position)
+                    || solver.map[new_player_position].intersects(Tiles::Wall)
+                    || has_box_at(new_player_position)
+                {
+                    continue;
+                }
+                let Some(new_box_index) = solver.floor().index(new_box_position) else {
+                    continue;
+                };
+
+                let mut new_boxes = self.boxes.clone();
+                new_boxes.remove(box_index);
+                new_boxes.insert(new_box_index);
+                let new_boxes_hash = self.boxes_hash
+                    ^ solver.zobrist().box_key(box_index)
+                    ^ solver.zobrist().box_key(new_box_index);
+                predecessors.push(
+                    BackwardState {
+                        player_position: new_player_position,
+                        boxes: new_boxes,
+                        boxes_hash: new_boxes_hash,
+                        pulls: self.pulls + 1,
+                        parent: Some(node),
+                        push: Some(Push {
+                            box_position: new_box_position,
+                            direction: Direction::try_from(box_position - new_box_position)
+                                .unwrap(),
+                            count: 1,
+                            goal_macro: None,
+                        }),
+                        heuristic: 0,
+                    }
+                    .with_heuristic(start_distances),
+                );
+            }
+        }
+        predecessors
+    }
+
+    /// Returns the exact key of the normalized state.
+    fn normalized_key(&self, solver: &Solver) -> StateKey {
+        let player_reachable_area = compute_reachable_area(self.player_position, |position| {
+            !solver.map[position].intersects(Tiles::Wall)
+                && solver
+                    .floor()
+                    .index(position)
+                    .is_none_or(|index| !self.boxes.contains(index))
+        });
+        StateKey::new(
+            self.boxes.clone(),
+            self.boxes_hash,
+            compute_area_anchor(&player_reachable_area).unwrap(),
+            solver.map.dimensions(),
+            solver.zobrist(),
+        )
+    }
+}
@@ -0,0 +1,114 @@
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use soukoban::Map;
+
+use crate::solve::{
+    external::ExternalSolver, optimizer::Metric, pattern_database::DeadlockPattern,
+    sokoban_solver::SokobanSolver, solver::*,
+};
+
+/// Implementation of the solver.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default, Serialize, Deserialize)]
+pub enum Backend {
+    /// The solver of this crate
+    #[default]
+    Builtin,
+
+    /// An external program, see [`ExternalSolver`]
+    External,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
+pub struct SolverConfig {
+    pub backend: Backend,
+    /// Program run by the external backend.
+    pub external_command: String,
+    /// Arguments of the program run by the external backend.
+    pub external_args: Vec<String>,
+    /// Maximum running time of the external program in seconds, 0 means unlimited.
+    pub external_time_limit: u64,
+    pub strategy: Strategy,
+    pub lower_bound_method: LowerBoundMethod,
+    /// Number of threads used by the solver.
+    pub threads: usize,
+    /// Maximum memory used by the solver in MiB, 0 means unlimited.
+    pub memory_limit: usize,
+    /// Whether the solver prunes the search with corrals.
+    pub corral_pruning: bool,
+    /// Whether the solver also searches backward from the solved state.
+    pub bidirectional: bool,
+    /// Whether the independent parts of a level are solved separately.
+    pub decomposition: bool,
+    /// Whether the weighted strategy keeps improving its solution with
+    /// decreasing weights.
+    pub anytime: bool,
+    /// Whether deadlock patterns proven by the solver are saved to the database.
+    pub persist_deadlock_patterns: bool,
+    /// Whether solutions are optimized in the background after the level is solved.
+    pub optimize_solutions: bool,
+    /// Metric minimized by the solution optimizer.
+    pub optimization_metric: Metric,
+}
+
+impl Default for SolverConfig {
+    fn default() -> Self {
+        Self {
+            backend: Backend::default(),
+            external_command: String::new(),
+            external_args: Vec::new(),
+            external_time_limit: 60,
+            strategy: Strategy::default(),
+            lower_bound_method: LowerBoundMethod::default(),
+            threads: 1,
+            memory_limit: 4096,
+            corral_pruning: true,
+            bidirectional: false,
+            decomposition: false,
+            anytime: false,
+            persist_deadlock_patterns: true,
+            optimize_solutions: true,
+            optimization_metric: Metric::default(),
+        }
+    }
+}
+
+impl SolverConfig {
+    /// Creates the solver selected by the configuration for the map.
+    ///
+    /// `deadlock_patterns` are patterns proven by previous searches of the map.
+    pub fn create_solver(
+        &self,
+        map: Map,
+        deadlock_patterns: Vec<DeadlockPattern>,
+    ) -> Box<dyn SokobanSolver> {
+        match self.backend {
+            Backend::Builtin => {
+                let mut solver = Solver::new(map, self.strategy, self.lower_bound_method);
+                solver.set_threads(self.threads);
+                solver.set_memory_limit(
+                    (self.memory_limit != 0).then(|| self.memory_limit * 1024 * 1024),
+                );
+                solver.set_corral_pruning(self.corral_pruning);
+                solver.set_bidirectional(self.bidirectional);
+                solver.set_decomposition(self.decomposition);
+                solver.set_anytime(self.anytime);
+                solver.add_deadlock_patterns(deadlock_patterns);
+                Box::new(solver)
+            }
+            Backend::External => {
+                let mut solver = ExternalSolver::new(
+                    map,
+                    self.external_command.clone(),
+                    self.external_args.clone(),
+                );
+                solver.set_time_limit(
+                    (self.external_time_limit != 0)
+                        .then(|| Duration::from_secs(self.external_time_limit)),
+                );
+                Box::new(solver)
+            }
+        }
+    }
+}
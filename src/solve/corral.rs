@@ -0,0 +1,208 @@
+use std::collections::{HashSet, VecDeque};
+
+use nalgebra::Vector2;
+use soukoban::{
+    Tiles,
+    direction::Direction,
+    path_finding::{compute_area_anchor, compute_reachable_area},
+};
+
+use crate::solve::{pattern_database::*, solver::*, state::*};
+
+/// Maximum number of boxes of a corral searched for deadlocks.
+const MAX_CORRAL_BOXES: usize = 6;
+
+/// Maximum number of states visited by a corral deadlock search.
+const MAX_CORRAL_STATES: usize = 100;
+
+/// An area the player can not reach, enclosed by boxes and walls.
+pub struct Corral {
+    /// Squares of the area which are not occupied by boxes.
+    area: HashSet<Vector2<i32>>,
+    /// Boxes adjacent to the area.
+    boxes: HashSet<Vector2<i32>>,
+    /// Boxes adjacent to both the area and the player reachable area.
+    barrier: HashSet<Vector2<i32>>,
+}
+
+impl Corral {
+    /// Finds all corrals of the state.
+    pub fn find_all(
+        state: &State,
+        player_reachable_area: &HashSet<Vector2<i32>>,
+        solver: &Solver,
+    ) -> Vec<Corral> {
+        let is_area = |position: Vector2<i32>| {
+            solver.floor().index(position).is_some()
+                && !player_reachable_area.contains(&position)
+                && !state.has_box_at(position, solver)
+        };
+
+        let mut corrals = Vec::new();
+        let mut visited = HashSet::new();
+        for index in 0..solver.floor().count() {
+            let position = solver.floor().position(index);
+            if !is_area(position) || visited.contains(&position) {
+                continue;
+            }
+            let area = compute_reachable_area(position, is_area);
+            visited.extend(area.iter().copied());
+
+            let mut boxes = HashSet::new();
+            let mut barrier = HashSet::new();
+            for square in &area {
+                for direction in Direction::iter() {
+                    let box_position = square + &direction.into();
+                    if !state.has_box_at(box_position, solver) {
+                        continue;
+                    }
+                    boxes.insert(box_position);
+                    if Direction::iter().any(|direction| {
+                        player_reachable_area.contains(&(box_position + &direction.into()))
+                    }) {
+                        barrier.insert(box_position);
+                    }
+                }
+            }
+            corrals.push(Corral {
+                area,
+                boxes,
+                barrier,
+            });
+        }
+        corrals
+    }
+
+    /// Returns the boxes which must be pushed next if the corral is a PI-corral.
+    ///
+    /// A PI-corral is a corral whose boxes can only be pushed into the corral
+    /// (I), and the player can perform all of these pushes now (P). One of
+    /// them must be pushed before the level can be solved, and pushes of other
+    /// boxes can be postponed without changing the number of pushes.
+    pub fn pi_barrier(
+        &self,
+        player_reachable_area: &HashSet<Vector2<i32>>,
+        solver: &Solver,
+    ) -> Option<&HashSet<Vector2<i32>>> {
+        if self.barrier.is_empty() || !self.is_relevant(solver) {
+            return None;
+        }
+        for box_position in &self.boxes {
+            for push_direction in Direction::iter() {
+                let new_box_position = box_position + &push_direction.into();
+                let player_position = box_position - &push_direction.into();
+                // Other boxes may move away before the corral is opened, so
+                // only walls and the boxes of the corral can block a push.
+                if solver.map[new_box_position].intersects(Tiles::Wall)
+                    || solver.push_distances().is_dead_square(new_box_position)
+                    || self.boxes.contains(&new_box_position)
+                    || solver.map[player_position].intersects(Tiles::Wall)
+                    || self.boxes.contains(&player_position)
+                    || self.area.contains(&player_position)
+                {
+                    continue;
+                }
+                if !self.area.contains(&new_box_position)
+                    || !player_reachable_area.contains(&player_position)
+                {
+                    return None;
+                }
+            }
+        }
+        Some(&self.barrier)
+    }
+
+    /// Returns a deadlock pattern if the boxes of the corral can not be pushed to goals.
+    ///
+    /// Boxes outside the corral are removed, which only gives the player
+    /// more freedom, so a deadlock found this way is also a deadlock of the
+    /// state. The search is considered successful if all corral boxes are on
+    /// goals or a box leaves the corral.
+    pub fn deadlock_pattern(&self, state: &State, solver: &Solver) -> Option<DeadlockPattern> {
+        if self.boxes.len() > MAX_CORRAL_BOXES || !self.is_relevant(solver) {
+            return None;
+        }
+
+        let is_free = |position: Vector2<i32>, boxes: &[Vector2<i32>]| {
+            !solver.map[position].intersects(Tiles::Wall) && !boxes.contains(&position)
+        };
+        let normalize = |player_position: Vector2<i32>, boxes: &mut Vec<Vector2<i32>>| {
+            boxes.sort_by_key(|position| (position.y, position.x));
+            let area = compute_reachable_area(player_position, |position| is_free(position, boxes));
+            compute_area_anchor(&area).unwrap()
+        };
+
+        let mut boxes: Vec<_> = self.boxes.iter().copied().collect();
+        if boxes
+            .iter()
+            .all(|position| solver.map[*position].intersects(Tiles::Goal))
+        {
+            return None;
+        }
+        let initial_player_position = normalize(state.player_position, &mut boxes);
+        let mut visited = HashSet::from([(boxes.clone(), initial_player_position)]);
+        let mut queue = VecDeque::from([(boxes, initial_player_position)]);
+        while let Some((boxes, player_position)) = queue.pop_front() {
+            if visited.len() > MAX_CORRAL_STATES {
+                return None;
+            }
+            let player_reachable_area =
+                compute_reachable_area(player_position, |position| is_free(position, &boxes));
+            for (index, box_position) in boxes.iter().enumerate() {
+                for push_direction in Direction::iter() {
+                    let new_box_position = box_position + &push_direction.into();
+                    if !is_free(new_box_position, &boxes)
+                        || solver.push_distances().is_dead_square(new_box_position)
+                        || !player_reachable_area.contains(&(box_position - &push_direction.into()))
+                    {
+                        continue;
+                    }
+                    if !self.area.contains(&new_box_position)
+                        && !self.boxes.contains(&new_box_position)
+                    {
+                        return None;
+                    }
+
+                    let mut new_boxes = boxes.clone();
+                    new_boxes[index] = new_box_position;
+                    if new_boxes
+                        .iter()
+                        .all(|position| solver.map[*position].intersects(Tiles::Goal))
+                    {
+                        return None;
+                    }
+                    let new_player_position = normalize(*box_position, &mut new_boxes);
+                    if visited.insert((new_boxes.clone(), new_player_position)) {
+                        queue.push_back((new_boxes, new_player_position));
+                    }
+                }
+            }
+        }
+        Some(DeadlockPattern::new(
+            self.boxes.iter().copied().collect(),
+            initial_player_position,
+        ))
+    }
+
+    /// Returns the boxes adjacent to the area of the corral.
+    pub fn boxes(&self) -> &HashSet<Vector2<i32>> {
+        &self.boxes
+    }
+
+    /// Checks if the box is adjacent to the area of the corral.
+    pub fn contains_box(&self, box_position: Vector2<i32>) -> bool {
+        self.boxes.contains(&box_position)
+    }
+
+    /// Checks if the corral contains a box which is not on a goal or a goal
+    /// without a box.
+    fn is_relevant(&self, solver: &Solver) -> bool {
+        self.boxes
+            .iter()
+            .any(|position| !solver.map[*position].intersects(Tiles::Goal))
+            || self
+                .area
+                .iter()
+                .any(|position| solver.map[*position].intersects(Tiles::Goal))
+    }
+}
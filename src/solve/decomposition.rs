@@ -0,0 +1,225 @@
+use std::{
+    collections::{HashSet, VecDeque},
+    str::FromStr,
+    time::{Duration, Instant},
+};
+
+use nalgebra::Vector2;
+use soukoban::{Actions, Level, Map, Tiles};
+
+use crate::{
+    board::Board,
+    solve::{solver::*, stats::SolverStats},
+    utils::box_pushable_paths_with_positions,
+};
+
+/// Boxes and goals of a part of a level.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Part {
+    pub box_positions: HashSet<Vector2<i32>>,
+    pub goal_positions: HashSet<Vector2<i32>>,
+}
+
+impl Part {
+    /// Returns the map of the part, where the boxes of the other parts are
+    /// walls and their goals are floor.
+    pub fn map(&self, map: &Map) -> Map {
+        let mut xsb = String::new();
+        for y in 0..map.dimensions().y {
+            for x in 0..map.dimensions().x {
+                let position = Vector2::new(x, y);
+                let is_goal = self.goal_positions.contains(&position);
+                xsb.push(
+                    if map[position].intersects(Tiles::Wall)
+                        || map.box_positions().contains(&position)
+                            && !self.box_positions.contains(&position)
+                    {
+                        '#'
+                    } else if position == map.player_position() {
+                        if is_goal { '+' } else { '@' }
+                    } else if self.box_positions.contains(&position) {
+                        if is_goal { '*' } else { '$' }
+                    } else if is_goal {
+                        '.'
+                    } else {
+                        ' '
+                    },
+                );
+            }
+            xsb.push('\n');
+        }
+        Level::from_str(&xsb).unwrap().into()
+    }
+}
+
+/// Partition of a level into parts whose boxes never interact.
+pub struct Decomposition {
+    pub parts: Vec<Part>,
+}
+
+impl Decomposition {
+    /// Partitions the boxes by the squares they can be pushed to, ignoring
+    /// the other boxes. Boxes which can be pushed to a common square are in
+    /// the same part, together with the goals they can be pushed to.
+    ///
+    /// Returns `None` if the level has a single part, or a part does not have
+    /// as many goals as boxes.
+    pub fn new(map: &Map) -> Option<Self> {
+        let mut box_positions: Vec<_> = map.box_positions().iter().copied().collect();
+        box_positions.sort_by_key(|position| (position.y, position.x));
+
+        // Boxes and squares of each part, the squares of the parts are disjoint.
+        let mut parts: Vec<(HashSet<_>, HashSet<_>)> = Vec::new();
+        for box_position in box_positions {
+            let mut squares: HashSet<_> =
+                box_pushable_paths_with_positions(map, &box_position, &HashSet::new())
+                    .into_keys()
+                    .map(|state| state.box_position)
+                    .collect();
+            squares.insert(box_position);
+            let mut part = (HashSet::from([box_position]), squares);
+            let mut index = 0;
+            while index < parts.len() {
+                if parts[index].1.is_disjoint(&part.1) {
+                    index += 1;
+                    continue;
+                }
+                let (boxes, squares) = parts.swap_remove(index);
+                part.0.extend(boxes);
+                part.1.extend(squares);
+            }
+            parts.push(part);
+        }
+        if parts.len() < 2 {
+            return None;
+        }
+
+        let parts: Vec<_> = parts
+            .into_iter()
+            .map(|(box_positions, squares)| Part {
+                goal_positions: map
+                    .goal_positions()
+                    .intersection(&squares)
+                    .copied()
+                    .collect(),
+                box_positions,
+            })
+            .collect();
+        let goal_count: usize = parts.iter().map(|part| part.goal_positions.len()).sum();
+        if goal_count != map.goal_positions().len()
+            || parts
+                .iter()
+                .any(|part| part.goal_positions.len() != part.box_positions.len())
+        {
+            return None;
+        }
+        Some(Self { parts })
+    }
+}
+
+/// State of a search over the parts of a decomposed level, kept between calls.
+///
+/// The parts are solved one after another, with the boxes of the other parts
+/// as walls. A part which can not be solved is retried after the others,
+/// since it may be blocked by their boxes.
+pub struct DecomposedSearch {
+    /// Board with the actions of the solved parts.
+    board: Board,
+    /// Parts which are not solved yet, the first one is being searched.
+    parts: VecDeque<Part>,
+    solver: Option<Box<Solver>>,
+    /// Number of parts which have failed since the last solved part.
+    failures: usize,
+    /// Progress of the finished searches.
+    stats: SolverStats,
+}
+
+impl DecomposedSearch {
+    /// Creates a new search, returns `None` if the level can not be decomposed
+    /// into several unsolved parts.
+    pub fn new(map: &Map) -> Option<Self> {
+        let mut parts = Decomposition::new(map)?.parts;
+        // Solved parts only add the setup cost of their solvers.
+        parts.retain(|part| part.box_positions != part.goal_positions);
+        if parts.len() < 2 {
+            return None;
+        }
+        Some(Self {
+            board: Board::with_map(map.clone()),
+            parts: parts.into(),
+            solver: None,
+            failures: 0,
+            stats: SolverStats::default(),
+        })
+    }
+
+    /// Continues searching the parts, returns the merged actions once every
+    /// part is solved.
+    ///
+    /// `create_solver` creates the solver of the map of a part.
+    pub fn search(
+        &mut self,
+        timeout: Duration,
+        create_solver: impl Fn(Map) -> Solver,
+    ) -> Result<Actions, SolveError> {
+        let timer = Instant::now();
+        while let Some(part) = self.parts.front() {
+            let solver = self
+                .solver
+                .get_or_insert_with(|| Box::new(create_solver(part.map(&self.board.map))));
+            match solver.search(timeout.saturating_sub(timer.elapsed())) {
+                Ok(actions) => {
+                    self.finish_part();
+                    for action in &*actions {
+                        self.board.do_action(action.direction());
+                    }
+                    self.parts.pop_front();
+                    self.failures = 0;
+                }
+                Err(SolveError::NoSolution) => {
+                    self.finish_part();
+                    self.failures += 1;
+                    if self.failures >= self.parts.len() {
+                        return Err(SolveError::NoSolution);
+                    }
+                    self.parts.rotate_left(1);
+                }
+                Err(error) => return Err(error),
+            }
+        }
+        if !self.board.is_solved() {
+            return Err(SolveError::NoSolution);
+        }
+        Ok(self.board.actions().clone())
+    }
+
+    /// Adds the progress of the searches of the parts to the stats.
+    pub fn add_stats(&self, stats: &mut SolverStats) {
+        for part_stats in
+            std::iter::once(self.stats).chain(self.solver.as_ref().map(|solver| solver.stats()))
+        {
+            stats.expanded_nodes += part_stats.expanded_nodes;
+            stats.generated_nodes += part_stats.generated_nodes;
+            stats.duplicates += part_stats.duplicates;
+            stats.pruning += part_stats.pruning;
+            stats.open_nodes += part_stats.open_nodes;
+            stats.visited_nodes += part_stats.visited_nodes;
+            stats.memory_usage += part_stats.memory_usage;
+            if part_stats.best_lower_bound.is_some() {
+                stats.best_lower_bound = part_stats.best_lower_bound;
+            }
+        }
+    }
+
+    /// Drops the solver of the current part, keeping its progress.
+    fn finish_part(&mut self) {
+        let Some(solver) = self.solver.take() else {
+            return;
+        };
+        let part_stats = solver.stats();
+        self.stats.expanded_nodes += part_stats.expanded_nodes;
+        self.stats.generated_nodes += part_stats.generated_nodes;
+        self.stats.duplicates += part_stats.duplicates;
+        self.stats.pruning += part_stats.pruning;
+    }
+}
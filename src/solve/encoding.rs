@@ -0,0 +1,225 @@
+use std::hash::{Hash, Hasher};
+
+use nalgebra::Vector2;
+use soukoban::{Map, Tiles};
+
+/// Maps the floor squares of a map to consecutive indices.
+pub struct FloorIndex {
+    dimensions: Vector2<i32>,
+    indices: Vec<u32>,
+    positions: Vec<Vector2<i32>>,
+}
+
+impl FloorIndex {
+    /// Creates a new index of the squares a box can occupy in the map.
+    pub fn new(map: &Map) -> Self {
+        let dimensions = map.dimensions();
+        let mut indices = vec![u32::MAX; (dimensions.x * dimensions.y) as usize];
+        let mut positions = Vec::new();
+        for y in 0..dimensions.y {
+            for x in 0..dimensions.x {
+                let position = Vector2::new(x, y);
+                if map[position].intersects(Tiles::Wall)
+                    || !map[position].intersects(Tiles::Floor | Tiles::Goal | Tiles::Box)
+                {
+                    continue;
+                }
+                indices[(y * dimensions.x + x) as usize] = positions.len() as u32;
+                positions.push(position);
+            }
+        }
+        Self {
+            dimensions,
+            indices,
+            positions,
+        }
+    }
+
+    /// Returns the index of the floor square at the position, or `None` if it is not a floor square.
+    pub fn index(&self, position: Vector2<i32>) -> Option<usize> {
+        if position.x < 0
+            || position.y < 0
+            || position.x >= self.dimensions.x
+            || position.y >= self.dimensions.y
+        {
+            return None;
+        }
+        let index = self.indices[(position.y * self.dimensions.x + position.x) as usize];
+        (index != u32::MAX).then_some(index as usize)
+    }
+
+    /// Returns the position of the floor square with the index.
+    pub fn position(&self, index: usize) -> Vector2<i32> {
+        self.positions[index]
+    }
+
+    /// Returns the number of floor squares.
+    pub fn count(&self) -> usize {
+        self.positions.len()
+    }
+}
+
+/// A set of box positions stored as a bitset over the floor squares.
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub struct BoxSet(Box<[u64]>);
+
+impl BoxSet {
+    /// Creates an empty set for a map with `len` floor squares.
+    pub fn new(len: usize) -> Self {
+        Self(vec![0; len.div_ceil(64)].into_boxed_slice())
+    }
+
+    /// Creates a set containing the box positions.
+    pub fn from_positions<'a>(
+        floor: &FloorIndex,
+        positions: impl IntoIterator<Item = &'a Vector2<i32>>,
+    ) -> Self {
+        let mut instance = Self::new(floor.count());
+        for position in positions {
+            instance.insert(
+                floor
+                    .index(*position)
+                    .expect("box must be on a floor square"),
+            );
+        }
+        instance
+    }
+
+    pub fn insert(&mut self, index: usize) {
+        self.0[index / 64] |= 1 << (index % 64);
+    }
+
+    pub fn remove(&mut self, index: usize) {
+        self.0[index / 64] &= !(1 << (index % 64));
+    }
+
+    pub fn contains(&self, index: usize) -> bool {
+        self.0[index / 64] & (1 << (index % 64)) != 0
+    }
+
+    /// Returns an iterator over the indices of the floor squares occupied by boxes.
+    pub fn iter(&self) -> impl Iterator<Item = usize> + '_ {
+        bits(self.0.iter().copied())
+    }
+
+    /// Returns the set of the boxes which are in either set.
+    pub fn union(&self, other: &BoxSet) -> BoxSet {
+        Self(
+            self.0
+                .iter()
+                .zip(other.0.iter())
+                .map(|(a, b)| a | b)
+                .collect(),
+        )
+    }
+
+    /// Returns an iterator over the indices of the boxes which are not in the other set.
+    pub fn difference<'a>(&'a self, other: &'a BoxSet) -> impl Iterator<Item = usize> + 'a {
+        bits(self.0.iter().zip(other.0.iter()).map(|(a, b)| a & !b))
+    }
+
+    /// Returns the number of boxes which are not in the other set.
+    pub fn difference_count(&self, other: &BoxSet) -> usize {
+        self.0
+            .iter()
+            .zip(other.0.iter())
+            .map(|(a, b)| (a & !b).count_ones() as usize)
+            .sum()
+    }
+}
+
+/// Returns an iterator over the indices of the set bits of the words.
+fn bits(words: impl Iterator<Item = u64>) -> impl Iterator<Item = usize> {
+    words.enumerate().flat_map(|(word_index, word)| {
+        let mut word = word;
+        std::iter::from_fn(move || {
+            if word == 0 {
+                return None;
+            }
+            let bit = word.trailing_zeros() as usize;
+            word &= word - 1;
+            Some(word_index * 64 + bit)
+        })
+    })
+}
+
+/// Random keys used to hash states incrementally.
+pub struct Zobrist {
+    boxes: Vec<u64>,
+    players: Vec<u64>,
+}
+
+impl Zobrist {
+    /// Creates random keys for every floor square and every square of the map.
+    pub fn new(floor: &FloorIndex, dimensions: Vector2<i32>) -> Self {
+        // SplitMix64, a fixed seed keeps hashes reproducible between runs.
+        let mut seed: u64 = 0x2545_f491_4f6c_dd1d;
+        let mut next = move || {
+            seed = seed.wrapping_add(0x9e37_79b9_7f4a_7c15);
+            let mut z = seed;
+            z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+            z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+            z ^ (z >> 31)
+        };
+        Self {
+            boxes: (0..floor.count()).map(|_| next()).collect(),
+            players: (0..dimensions.x * dimensions.y).map(|_| next()).collect(),
+        }
+    }
+
+    /// Returns the key of a box on the floor square with the index.
+    pub fn box_key(&self, index: usize) -> u64 {
+        self.boxes[index]
+    }
+
+    /// Returns the hash of all boxes in the set.
+    pub fn boxes_hash(&self, boxes: &BoxSet) -> u64 {
+        boxes
+            .iter()
+            .fold(0, |hash, index| hash ^ self.box_key(index))
+    }
+
+    fn player_key(&self, index: usize) -> u64 {
+        self.players[index]
+    }
+}
+
+/// An exact and compact representation of a normalized state.
+///
+/// Two keys are equal only if both box positions and player anchors are
+/// equal, hash collisions therefore never merge distinct states.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct StateKey {
+    boxes: BoxSet,
+    player: u32,
+    hash: u64,
+}
+
+impl StateKey {
+    /// Creates a new key from the boxes, their Zobrist hash and the normalized player position.
+    pub fn new(
+        boxes: BoxSet,
+        boxes_hash: u64,
+        player_position: Vector2<i32>,
+        dimensions: Vector2<i32>,
+        zobrist: &Zobrist,
+    ) -> Self {
+        let player = (player_position.y * dimensions.x + player_position.x) as usize;
+        Self {
+            boxes,
+            player: player as u32,
+            hash: boxes_hash ^ zobrist.player_key(player),
+        }
+    }
+
+    /// Returns the precomputed Zobrist hash of the state.
+    pub fn zobrist_hash(&self) -> u64 {
+        self.hash
+    }
+}
+
+impl Hash for StateKey {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        state.write_u64(self.hash);
+    }
+}
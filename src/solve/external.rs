@@ -0,0 +1,239 @@
+use std::{
+    io::{BufRead, BufReader, Write},
+    process::{Child, Command, Stdio},
+    str::FromStr,
+    sync::mpsc::{self, Receiver, RecvTimeoutError},
+    thread,
+    time::{Duration, Instant},
+};
+
+use soukoban::{Actions, Map};
+
+use crate::{
+    board::Board,
+    solve::{sokoban_solver::SokobanSolver, solver::SolveError, stats::SolverStats},
+};
+
+/// Adapter for a solver running as an external program.
+///
+/// The map is written to the standard input of the program in XSB format,
+/// which is then closed. Each line of the standard output is either:
+///
+/// - A solution in LURD format. The last one is used.
+/// - A progress line, `progress` followed by `key=value` pairs whose keys are
+///   fields of [`SolverStats`], such as `progress expanded_nodes=1024`.
+///
+/// Other lines are ignored. If the program exits without printing a valid
+/// solution, the level is reported as having no solution, and if it cannot be
+/// started, [`SolveError::SpawnFailed`] is reported. The program is killed
+/// when the search is cancelled or exceeds the time limit.
+pub struct ExternalSolver {
+    map: Map,
+    command: String,
+    args: Vec<String>,
+    /// Maximum running time of the program.
+    time_limit: Option<Duration>,
+    child: Option<Child>,
+    /// Lines read from the standard output of the program.
+    lines: Option<Receiver<String>>,
+    /// Last solution printed by the program.
+    solution: Option<Actions>,
+    stats: SolverStats,
+    timer: Option<Instant>,
+    result: Option<Result<Actions, SolveError>>,
+}
+
+impl ExternalSolver {
+    /// Creates a new solver running the command with the arguments.
+    pub fn new(map: Map, command: String, args: Vec<String>) -> Self {
+        Self {
+            map,
+            command,
+            args,
+            time_limit: None,
+            child: None,
+            lines: None,
+            solution: None,
+            stats: SolverStats::default(),
+            timer: None,
+            result: None,
+        }
+    }
+
+    /// Sets the maximum running time of the program, `None` means unlimited.
+    ///
+    /// Once it is exceeded, the program is killed and the last solution it
+    /// printed is used.
+    pub fn set_time_limit(&mut self, time_limit: Option<Duration>) {
+        self.time_limit = time_limit;
+    }
+
+    fn handle_line(&mut self, line: &str) {
+        let line = line.trim();
+        if let Some(pairs) = line.strip_prefix("progress") {
+            for (key, value) in pairs
+                .split_whitespace()
+                .filter_map(|pair| pair.split_once('='))
+            {
+                let Ok(value) = value.parse() else {
+                    continue;
+                };
+                match key {
+                    "expanded_nodes" => self.stats.expanded_nodes = value,
+                    "generated_nodes" => self.stats.generated_nodes = value,
+                    "duplicates" => self.stats.duplicates = value,
+                    "open_nodes" => self.stats.open_nodes = value,
+                    "visited_nodes" => self.stats.visited_nodes = value,
+                    "memory_usage" => self.stats.memory_usage = value,
+                    "best_lower_bound" => self.stats.best_lower_bound = Some(value),
+                    _ => {}
+                }
+            }
+        } else if !line.is_empty() && line.chars().all(|char| "lurdLURD".contains(char)) {
+            self.solution = Actions::from_str(line).ok();
+        }
+    }
+
+    /// Replays the solution on a board, returns the actions if it solves the level.
+    fn validate(&self, solution: &Actions) -> Option<Actions> {
+        let mut board = Board::with_map(self.map.clone());
+        for action in &**solution {
+            if !board.moveable(action.direction()) {
+                return None;
+            }
+            board.do_action(action.direction());
+        }
+        board.is_solved().then(|| board.actions().clone())
+    }
+
+    fn finish(&mut self, result: Result<Actions, SolveError>) {
+        self.update_elapsed();
+        self.timer = None;
+        self.result = Some(result);
+    }
+
+    fn update_elapsed(&mut self) {
+        if let Some(timer) = self.timer {
+            self.stats.elapsed = timer.elapsed();
+        }
+    }
+}
+
+impl SokobanSolver for ExternalSolver {
+    fn start(&mut self) {
+        self.cancel();
+        self.solution = None;
+        self.stats = SolverStats::default();
+        self.result = None;
+        self.timer = Some(Instant::now());
+
+        let child = Command::new(&self.command)
+            .args(&self.args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn();
+        let Ok(mut child) = child else {
+            self.finish(Err(SolveError::SpawnFailed));
+            return;
+        };
+        // The program may exit without reading the map.
+        let mut stdin = child.stdin.take().unwrap();
+        let _ = writeln!(stdin, "{}", self.map);
+        drop(stdin);
+
+        let stdout = child.stdout.take().unwrap();
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || {
+            for line in BufReader::new(stdout).lines() {
+                let Ok(line) = line else {
+                    return;
+                };
+                if sender.send(line).is_err() {
+                    return;
+                }
+            }
+        });
+        self.child = Some(child);
+        self.lines = Some(receiver);
+    }
+
+    fn step(&mut self, budget: Duration) -> Result<Actions, SolveError> {
+        if let Some(result) = &self.result {
+            return result.clone();
+        }
+        let Some(lines) = self.lines.take() else {
+            return Err(SolveError::Timeout);
+        };
+        let expiry = self
+            .time_limit
+            .zip(self.timer)
+            .map(|(limit, timer)| timer + limit);
+        let mut deadline = Instant::now() + budget;
+        if let Some(expiry) = expiry {
+            deadline = deadline.min(expiry);
+        }
+        let mut expired = false;
+        loop {
+            match lines.recv_timeout(deadline.saturating_duration_since(Instant::now())) {
+                Ok(line) => self.handle_line(&line),
+                Err(RecvTimeoutError::Timeout) => {
+                    if expiry.is_some_and(|expiry| Instant::now() >= expiry) {
+                        expired = true;
+                        break;
+                    }
+                    self.lines = Some(lines);
+                    self.update_elapsed();
+                    return Err(SolveError::Timeout);
+                }
+                Err(RecvTimeoutError::Disconnected) => break,
+            }
+        }
+        // The standard output is closed, the program has exited or is exiting.
+        if let Some(mut child) = self.child.take() {
+            if expired {
+                let _ = child.kill();
+            }
+            let _ = child.wait();
+        }
+        let solution = self
+            .solution
+            .take()
+            .and_then(|solution| self.validate(&solution));
+        let result = solution.ok_or(if expired {
+            SolveError::Timeout
+        } else {
+            SolveError::NoSolution
+        });
+        self.finish(result.clone());
+        result
+    }
+
+    fn cancel(&mut self) {
+        if let Some(mut child) = self.child.take() {
+            let _ = child.kill();
+            let _ = child.wait();
+        }
+        self.lines = None;
+        self.update_elapsed();
+        self.timer = None;
+    }
+
+    fn stats(&self) -> SolverStats {
+        let mut stats = self.stats;
+        if let Some(timer) = self.timer {
+            stats.elapsed = timer.elapsed();
+        }
+        stats
+    }
+
+    fn result(&self) -> Option<Result<Actions, SolveError>> {
+        self.result.clone()
+    }
+}
+
+impl Drop for ExternalSolver {
+    fn drop(&mut self) {
+        self.cancel();
+    }
+}
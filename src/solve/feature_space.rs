@@ -0,0 +1,248 @@
+use std::{
+    cmp::Ordering,
+    collections::{BTreeMap, BinaryHeap, HashSet},
+    ops::Bound,
+};
+
+use nalgebra::Vector2;
+use soukoban::{Tiles, direction::Direction, path_finding::compute_reachable_area};
+
+use crate::solve::{solver::*, state::*};
+
+/// Features a state is projected onto by the feature space search.
+///
+/// Lower values are better for all features.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub struct Features {
+    /// Number of boxes which are not on goals.
+    pub unpacked: usize,
+    /// Number of areas the boxes divide the floor into.
+    pub connectivity: usize,
+    /// Number of boxes blocking a passage between rooms.
+    pub room_connectivity: usize,
+    /// Number of empty goals which can no longer be entered because of packed boxes.
+    pub out_of_plan: usize,
+}
+
+impl Features {
+    /// Calculates the features of the state.
+    pub fn new(state: &State, solver: &Solver) -> Self {
+        let mut unpacked = 0;
+        let mut room_connectivity = 0;
+        for index in state.boxes.iter() {
+            let position = solver.floor().position(index);
+            if solver.map[position].intersects(Tiles::Goal) {
+                continue;
+            }
+            unpacked += 1;
+            if is_passage(position, solver) {
+                room_connectivity += 1;
+            }
+        }
+
+        let mut connectivity = 0;
+        let mut visited = HashSet::new();
+        for index in 0..solver.floor().count() {
+            let position = solver.floor().position(index);
+            if state.has_box_at(position, solver) || visited.contains(&position) {
+                continue;
+            }
+            visited.extend(compute_reachable_area(position, |position| {
+                !solver.map[position].intersects(Tiles::Wall) && !state.has_box_at(position, solver)
+            }));
+            connectivity += 1;
+        }
+
+        let is_packed = |position: Vector2<i32>| {
+            solver.map[position].intersects(Tiles::Wall)
+                || solver.map[position].intersects(Tiles::Goal)
+                    && state.has_box_at(position, solver)
+        };
+        let out_of_plan = solver
+            .map
+            .goal_positions()
+            .iter()
+            .filter(|goal_position| !state.has_box_at(**goal_position, solver))
+            .filter(|goal_position| {
+                Direction::iter().all(|direction| {
+                    let box_position = *goal_position - &direction.into();
+                    is_packed(box_position) || is_packed(box_position - &direction.into())
+                })
+            })
+            .count();
+
+        Self {
+            unpacked,
+            connectivity,
+            room_connectivity,
+            out_of_plan,
+        }
+    }
+
+    /// Returns the features as an array, in the order of the advisors.
+    fn values(&self) -> [usize; 4] {
+        [
+            self.unpacked,
+            self.connectivity,
+            self.room_connectivity,
+            self.out_of_plan,
+        ]
+    }
+}
+
+/// Checks if a box on the position blocks a one square wide passage.
+fn is_passage(position: Vector2<i32>, solver: &Solver) -> bool {
+    let is_wall =
+        |direction: Direction| solver.map[position + &direction.into()].intersects(Tiles::Wall);
+    is_wall(Direction::Up) && is_wall(Direction::Down)
+        || is_wall(Direction::Left) && is_wall(Direction::Right)
+}
+
+/// A state waiting to be expanded in a feature cell.
+struct Candidate {
+    /// Number of pushes on the path to the state which were not suggested by an advisor.
+    weight: usize,
+    features: Features,
+    state: State,
+}
+
+impl PartialEq for Candidate {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Candidate {}
+
+impl Ord for Candidate {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.weight
+            .cmp(&other.weight)
+            .reverse()
+            .then_with(|| self.state.cmp(&other.state))
+    }
+}
+
+impl PartialOrd for Candidate {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// State of a Festival style feature space search, kept between calls.
+///
+/// States are grouped into cells by their features. The search cycles over
+/// the cells and expands the state with the lowest weight of each cell, so
+/// that progress on any feature is explored even if the others get worse.
+pub struct FeatureSpace {
+    cells: BTreeMap<Features, BinaryHeap<Candidate>>,
+    /// The cell expanded last.
+    cursor: Option<Features>,
+}
+
+impl FeatureSpace {
+    /// Creates a new feature space containing the initial state.
+    pub fn new(state: State, solver: &Solver) -> Self {
+        let mut instance = Self {
+            cells: BTreeMap::new(),
+            cursor: None,
+        };
+        instance.insert(Candidate {
+            weight: 0,
+            features: Features::new(&state, solver),
+            state,
+        });
+        instance
+    }
+
+    /// Removes the state with the lowest weight from the cell following the
+    /// last expanded cell.
+    ///
+    /// Returns the state, its weight and features.
+    pub fn pop(&mut self) -> Option<(State, usize, Features)> {
+        let features = match self.cursor {
+            Some(cursor) => self
+                .cells
+                .range((Bound::Excluded(cursor), Bound::Unbounded))
+                .next()
+                .or_else(|| self.cells.iter().next())
+                .map(|(features, _)| *features)?,
+            None => *self.cells.keys().next()?,
+        };
+        self.cursor = Some(features);
+
+        let cell = self.cells.get_mut(&features).unwrap();
+        let candidate = cell.pop().unwrap();
+        if cell.is_empty() {
+            self.cells.remove(&features);
+        }
+        Some((candidate.state, candidate.weight, candidate.features))
+    }
+
+    /// Adds the successors of an expanded state.
+    ///
+    /// Each advisor suggests the successor which improves its feature the
+    /// most. Suggested successors keep the weight of the expanded state, the
+    /// others are one heavier.
+    pub fn extend(
+        &mut self,
+        weight: usize,
+        features: Features,
+        successors: Vec<State>,
+        solver: &Solver,
+    ) {
+        let successors: Vec<_> = successors
+            .into_iter()
+            .map(|state| (Features::new(&state, solver), state))
+            .collect();
+
+        let mut advised = vec![false; successors.len()];
+        for advisor in 0..features.values().len() {
+            let parent_value = features.values()[advisor];
+            if let Some((index, _)) = successors
+                .iter()
+                .enumerate()
+                .filter(|(_, (successor, _))| successor.values()[advisor] < parent_value)
+                .min_by_key(|(_, (successor, state))| {
+                    (successor.values()[advisor], state.heuristic())
+                })
+            {
+                advised[index] = true;
+            }
+        }
+
+        for ((features, state), advised) in successors.into_iter().zip(advised) {
+            self.insert(Candidate {
+                weight: if advised { weight } else { weight + 1 },
+                features,
+                state,
+            });
+        }
+    }
+
+    /// Returns the number of states waiting to be expanded.
+    pub fn state_count(&self) -> usize {
+        self.cells.values().map(BinaryHeap::len).sum()
+    }
+
+    /// Returns the approximate memory used by the states, in bytes.
+    pub fn memory_usage(&self, solver: &Solver) -> usize {
+        self.state_count() * solver.state_size()
+    }
+
+    /// Returns the best state of the best cell.
+    pub fn best_state(&self) -> Option<&State> {
+        self.cells
+            .values()
+            .next()
+            .and_then(|cell| cell.peek())
+            .map(|candidate| &candidate.state)
+    }
+
+    fn insert(&mut self, candidate: Candidate) {
+        self.cells
+            .entry(candidate.features)
+            .or_default()
+            .push(candidate);
+    }
+}
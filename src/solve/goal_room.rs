@@ -0,0 +1,195 @@
+use std::collections::{HashMap, HashSet, VecDeque, hash_map::Entry};
+
+use nalgebra::Vector2;
+use soukoban::{
+    Action, Actions, Map, Tiles, direction::Direction, path_finding::compute_reachable_area,
+};
+
+/// Positions of the box and the player.
+type PullState = (Vector2<i32>, Vector2<i32>);
+
+/// Pushes moving a box from the entrance of a goal room to a goal.
+#[derive(Clone, Debug)]
+pub struct GoalMacro {
+    /// Position of the player before the first push, outside the room.
+    pub player_position: Vector2<i32>,
+    /// Actions moving the box from the entrance to the goal.
+    pub actions: Actions,
+    pub goal: Vector2<i32>,
+    /// Position of the player after the last push.
+    pub final_player_position: Vector2<i32>,
+    pub pushes: usize,
+}
+
+/// A room containing all goals, which boxes can only enter through a single
+/// square.
+///
+/// Boxes entering the room fill the goals in a packing order computed by a
+/// reverse search, so that no goal blocks the way to the goals filled later.
+pub struct GoalRoom {
+    /// Squares of the room, excluding the entrance.
+    squares: HashSet<Vector2<i32>>,
+    pub entrance: Vector2<i32>,
+    /// Macro filling each goal, in packing order.
+    macros: Vec<GoalMacro>,
+}
+
+impl GoalRoom {
+    /// Detects the goal room of the map and computes its packing order.
+    ///
+    /// Returns `None` if the goals are not in a single room with a single
+    /// entrance, the room contains boxes, or the goals can not be filled one
+    /// after another from the entrance.
+    pub fn new(map: &Map) -> Option<Self> {
+        let floor = compute_reachable_area(map.player_position(), |position| {
+            !map[position].intersects(Tiles::Wall)
+        });
+        let goals = map.goal_positions();
+
+        // The smallest room is used, so that the entrance is the square
+        // closest to the goals.
+        let (entrance, squares) = floor
+            .iter()
+            .filter(|position| !goals.contains(position))
+            .filter_map(|&entrance| {
+                let start = *goals.iter().next()?;
+                let squares = compute_reachable_area(start, |position| {
+                    position != entrance && !map[position].intersects(Tiles::Wall)
+                });
+                (goals.is_subset(&squares)
+                    && !squares.contains(&map.player_position())
+                    && squares.is_disjoint(map.box_positions()))
+                .then_some((entrance, squares))
+            })
+            .min_by_key(|(entrance, squares)| (squares.len(), entrance.y, entrance.x))?;
+
+        let mut room = Self {
+            squares,
+            entrance,
+            macros: Vec::new(),
+        };
+
+        // Reverse search: the goal filled last is the first whose box can be
+        // pulled out of the room while the other goals are still filled.
+        let mut filled: Vec<_> = goals.iter().copied().collect();
+        filled.sort_by_key(|position| (position.y, position.x));
+        while !filled.is_empty() {
+            let (index, goal_macro) = filled.iter().enumerate().find_map(|(index, &goal)| {
+                let obstacles: HashSet<_> = filled
+                    .iter()
+                    .copied()
+                    .filter(|&position| position != goal)
+                    .collect();
+                room.goal_macro_to(map, goal, &obstacles)
+                    .map(|goal_macro| (index, goal_macro))
+            })?;
+            filled.remove(index);
+            room.macros.push(goal_macro);
+        }
+        room.macros.reverse();
+        Some(room)
+    }
+
+    /// Checks if the position is inside the room, excluding the entrance.
+    pub fn contains(&self, position: Vector2<i32>) -> bool {
+        self.squares.contains(&position)
+    }
+
+    /// Returns the macro filling the goal at the index of the packing order.
+    pub fn goal_macro(&self, index: usize) -> &GoalMacro {
+        &self.macros[index]
+    }
+
+    /// Returns the index of the next macro if the boxes in the room are
+    /// exactly on the goals filled before it.
+    ///
+    /// `boxes_in_room` is the number of boxes inside the room.
+    pub fn next_macro(
+        &self,
+        boxes_in_room: usize,
+        has_box_at: impl Fn(Vector2<i32>) -> bool,
+    ) -> Option<usize> {
+        (boxes_in_room < self.macros.len()
+            && self.macros[..boxes_in_room]
+                .iter()
+                .all(|goal_macro| has_box_at(goal_macro.goal)))
+        .then_some(boxes_in_room)
+    }
+
+    /// Searches for the pulls moving a box from the goal to the entrance, with
+    /// boxes on the obstacles. Returns them as the pushes filling the goal.
+    fn goal_macro_to(
+        &self,
+        map: &Map,
+        goal: Vector2<i32>,
+        obstacles: &HashSet<Vector2<i32>>,
+    ) -> Option<GoalMacro> {
+        let walkable = |position: Vector2<i32>, box_position: Vector2<i32>| {
+            position != box_position
+                && (self.contains(position) || position == self.entrance)
+                && !obstacles.contains(&position)
+        };
+
+        // States are the positions of the box and the player. Each state
+        // stores its parent and the action leading from the state to its
+        // parent in the forward direction.
+        let mut parents: HashMap<PullState, Option<(PullState, Action)>> = HashMap::new();
+        let mut queue = VecDeque::new();
+        for direction in Direction::iter() {
+            let player_position = goal + &direction.into();
+            if walkable(player_position, goal) {
+                parents.insert((goal, player_position), None);
+                queue.push_back((goal, player_position));
+            }
+        }
+
+        while let Some(state) = queue.pop_front() {
+            let (box_position, player_position) = state;
+            for direction in Direction::iter() {
+                let next_player_position = player_position + &direction.into();
+                let forward_direction =
+                    Direction::try_from(player_position - next_player_position).unwrap();
+                if box_position + &direction.into() != player_position {
+                    if walkable(next_player_position, box_position) {
+                        let next_state = (box_position, next_player_position);
+                        if let Entry::Vacant(entry) = parents.entry(next_state) {
+                            entry.insert(Some((state, Action::Move(forward_direction))));
+                            queue.push_back(next_state);
+                        }
+                    }
+                    continue;
+                }
+
+                // Pull the box, which follows the player.
+                if player_position == self.entrance
+                    && !self.contains(next_player_position)
+                    && !map[next_player_position].intersects(Tiles::Wall)
+                {
+                    // The box is on the entrance and the player outside the room.
+                    let mut actions = Actions::new();
+                    actions.push(Action::Push(forward_direction));
+                    let mut current = state;
+                    while let Some((parent, action)) = parents[&current] {
+                        actions.push(action);
+                        current = parent;
+                    }
+                    return Some(GoalMacro {
+                        player_position: next_player_position,
+                        pushes: actions.pushes(),
+                        actions,
+                        goal,
+                        final_player_position: current.1,
+                    });
+                }
+                if walkable(next_player_position, box_position) {
+                    let next_state = (player_position, next_player_position);
+                    if let Entry::Vacant(entry) = parents.entry(next_state) {
+                        entry.insert(Some((state, Action::Push(forward_direction))));
+                        queue.push_back(next_state);
+                    }
+                }
+            }
+        }
+        None
+    }
+}
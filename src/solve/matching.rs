@@ -0,0 +1,174 @@
+/// Cost of assigning a box to a goal it can not reach.
+const INFINITY: i32 = 100_000;
+
+/// A minimum-cost assignment between boxes and goals.
+///
+/// Computed with the Hungarian algorithm. The dual potentials are kept so that
+/// the assignment can be repaired with a single augmentation when only one box
+/// moved.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Matching {
+    /// Floor index of the box of each row.
+    rows: Vec<u32>,
+    /// Goal of each column.
+    columns: Vec<u32>,
+    /// Row assigned to each column (1-based, 0 means unassigned).
+    /// Column 0 is used by the algorithm as a virtual column.
+    assignment: Vec<u32>,
+    row_potentials: Vec<i32>,
+    column_potentials: Vec<i32>,
+}
+
+impl Matching {
+    /// Computes the matching between the boxes and the goals.
+    ///
+    /// `distances[goal][index]` is the distance of the floor square with the
+    /// index to the goal, `usize::MAX` if unreachable.
+    pub fn new(boxes: impl IntoIterator<Item = usize>, distances: &[Vec<usize>]) -> Self {
+        Self::with_goals(boxes, 0..distances.len(), distances)
+    }
+
+    /// Computes the matching between the boxes and a subset of the goals.
+    pub fn with_goals(
+        boxes: impl IntoIterator<Item = usize>,
+        goals: impl IntoIterator<Item = usize>,
+        distances: &[Vec<usize>],
+    ) -> Self {
+        let rows: Vec<u32> = boxes.into_iter().map(|index| index as u32).collect();
+        let columns: Vec<u32> = goals.into_iter().map(|goal| goal as u32).collect();
+        let mut instance = Self {
+            assignment: vec![0; columns.len() + 1],
+            row_potentials: vec![0; rows.len() + 1],
+            column_potentials: vec![0; columns.len() + 1],
+            rows,
+            columns,
+        };
+        instance.solve(distances);
+        instance
+    }
+
+    /// Returns the matching after the box at `from` has been moved to `to`.
+    pub fn moved(&self, from: usize, to: usize, distances: &[Vec<usize>]) -> Self {
+        let mut instance = self.clone();
+        let row = instance
+            .rows
+            .iter()
+            .position(|&index| index as usize == from)
+            .expect("box must be in the matching");
+        instance.rows[row] = to as u32;
+
+        // Free columns must keep zero potentials, which is only guaranteed if
+        // every column is assigned.
+        if instance.rows.len() != instance.columns.len() {
+            instance.assignment.fill(0);
+            instance.row_potentials.fill(0);
+            instance.column_potentials.fill(0);
+            instance.solve(distances);
+            return instance;
+        }
+
+        let row = row + 1;
+        if let Some(column) = (1..instance.assignment.len())
+            .find(|&column| instance.assignment[column] as usize == row)
+        {
+            instance.assignment[column] = 0;
+        }
+        // Restore dual feasibility of the moved row.
+        instance.row_potentials[row] = (1..instance.column_potentials.len())
+            .map(|column| {
+                instance.cost(row, column, distances) - instance.column_potentials[column]
+            })
+            .min()
+            .unwrap_or(0);
+        instance.augment(row, distances);
+        instance
+    }
+
+    /// Returns the total distance of the assignment, or `None` if some box can
+    /// not be assigned to a reachable goal.
+    pub fn total_distance(&self, distances: &[Vec<usize>]) -> Option<usize> {
+        if self.rows.len() > self.columns.len() {
+            return None;
+        }
+        let mut sum = 0;
+        for (column, &row) in self.assignment.iter().enumerate().skip(1) {
+            if row == 0 {
+                continue;
+            }
+            let cost = self.cost(row as usize, column, distances);
+            if cost >= INFINITY {
+                return None;
+            }
+            sum += cost as usize;
+        }
+        Some(sum)
+    }
+
+    fn solve(&mut self, distances: &[Vec<usize>]) {
+        if self.rows.len() > self.columns.len() {
+            return;
+        }
+        for row in 1..=self.rows.len() {
+            self.augment(row, distances);
+        }
+    }
+
+    /// Finds a shortest augmenting path from the unassigned row and updates
+    /// the assignment and potentials along it.
+    fn augment(&mut self, row: usize, distances: &[Vec<usize>]) {
+        let columns = self.column_potentials.len();
+        let mut min_slack = vec![i32::MAX / 2; columns];
+        let mut used = vec![false; columns];
+        let mut way = vec![0; columns];
+
+        self.assignment[0] = row as u32;
+        let mut current_column = 0;
+        loop {
+            used[current_column] = true;
+            let current_row = self.assignment[current_column] as usize;
+            let mut delta = i32::MAX / 2;
+            let mut next_column = 0;
+            for column in 1..columns {
+                if used[column] {
+                    continue;
+                }
+                let slack = self.cost(current_row, column, distances)
+                    - self.row_potentials[current_row]
+                    - self.column_potentials[column];
+                if slack < min_slack[column] {
+                    min_slack[column] = slack;
+                    way[column] = current_column;
+                }
+                if min_slack[column] < delta {
+                    delta = min_slack[column];
+                    next_column = column;
+                }
+            }
+            for column in 0..columns {
+                if used[column] {
+                    self.row_potentials[self.assignment[column] as usize] += delta;
+                    self.column_potentials[column] -= delta;
+                } else {
+                    min_slack[column] -= delta;
+                }
+            }
+            current_column = next_column;
+            if self.assignment[current_column] == 0 {
+                break;
+            }
+        }
+        while current_column != 0 {
+            let previous_column = way[current_column];
+            self.assignment[current_column] = self.assignment[previous_column];
+            current_column = previous_column;
+        }
+    }
+
+    /// Returns the cost of assigning the box of the row to the goal of the column.
+    fn cost(&self, row: usize, column: usize, distances: &[Vec<usize>]) -> i32 {
+        match distances[self.columns[column - 1] as usize][self.rows[row - 1] as usize] {
+            usize::MAX => INFINITY,
+            distance => distance as i32,
+        }
+    }
+}
@@ -1,2 +1,21 @@
+pub mod arena;
+pub mod benchmark;
+pub mod bidirectional;
+pub mod config;
+pub mod corral;
+pub mod decomposition;
+pub mod encoding;
+pub mod external;
+pub mod feature_space;
+pub mod goal_room;
+pub mod matching;
+pub mod optimizer;
+pub mod pattern_database;
+pub mod push_distance;
+pub mod sokoban_solver;
 pub mod solver;
 pub mod state;
+pub mod stats;
+pub mod transposition_table;
+pub mod tunnel;
+pub mod worker;
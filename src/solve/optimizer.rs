@@ -0,0 +1,351 @@
+use std::{
+    cmp::Reverse,
+    collections::{BinaryHeap, HashMap, VecDeque},
+    fmt,
+    time::{Duration, Instant},
+};
+
+use nalgebra::Vector2;
+use serde::{Deserialize, Serialize};
+use soukoban::{Action, Actions, Map, Tiles, direction::Direction, path_finding::find_path};
+
+use crate::{
+    board::Board,
+    solve::{arena::Push, encoding::*},
+};
+
+/// Metric minimized by the optimizer.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default, Serialize, Deserialize)]
+pub enum Metric {
+    /// Fewest moves, then fewest pushes
+    #[default]
+    Moves,
+
+    /// Fewest pushes, then fewest moves
+    Pushes,
+
+    /// Fewest box lines, then fewest moves
+    BoxLines,
+
+    /// Fewest box changes, then fewest moves
+    BoxChanges,
+}
+
+/// Measurements of a solution.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct Metrics {
+    pub moves: usize,
+    pub pushes: usize,
+    /// Number of runs of pushes in the same direction without moves in between.
+    pub box_lines: usize,
+    /// Number of times the pushed box differs from the previously pushed box.
+    pub box_changes: usize,
+}
+
+impl Metrics {
+    /// Measures the actions by replaying them on the map.
+    ///
+    /// Returns `None` if the actions do not solve the level.
+    pub fn new(map: &Map, actions: &Actions) -> Option<Self> {
+        let mut board = Board::with_map(map.clone());
+        let mut metrics = Self::default();
+        let mut previous_action: Option<Action> = None;
+        let mut last_pushed_box = None;
+        for action in &**actions {
+            let box_position = board.map.player_position() + &action.direction().into();
+            board.do_action(action.direction());
+            metrics.moves += 1;
+            if action.is_push() {
+                metrics.pushes += 1;
+                if !previous_action.is_some_and(|previous| {
+                    previous.is_push() && previous.direction() == action.direction()
+                }) {
+                    metrics.box_lines += 1;
+                }
+                if last_pushed_box != Some(box_position) {
+                    metrics.box_changes += 1;
+                }
+                last_pushed_box = Some(box_position + &action.direction().into());
+            }
+            previous_action = Some(*action);
+        }
+        (board.is_solved() && board.actions().len() == actions.len()).then_some(metrics)
+    }
+
+    /// Returns the values compared by the metric, most significant first.
+    pub fn key(&self, metric: Metric) -> (usize, usize, usize) {
+        match metric {
+            Metric::Moves => (self.moves, self.pushes, 0),
+            Metric::Pushes => (self.pushes, self.moves, 0),
+            Metric::BoxLines => (self.box_lines, self.moves, self.pushes),
+            Metric::BoxChanges => (self.box_changes, self.moves, self.pushes),
+        }
+    }
+}
+
+impl fmt::Display for Metrics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "moves: {}, pushes: {}, box lines: {}, box changes: {}",
+            self.moves, self.pushes, self.box_lines, self.box_changes
+        )
+    }
+}
+
+/// A box configuration reached by the vicinity search.
+struct SearchNode {
+    boxes: BoxSet,
+    player_position: Vector2<i32>,
+    /// Direction of the push leading to the node.
+    last_direction: Option<Direction>,
+    metrics: Metrics,
+    parent: Option<usize>,
+    push: Option<Push>,
+}
+
+/// Improves existing solutions with a vicinity search.
+///
+/// The search is restricted to the box configurations which differ from a
+/// configuration of the solution by a few boxes moved by a few squares, and
+/// finds the best solution within them under the metric.
+pub struct Optimizer {
+    map: Map,
+    floor: FloorIndex,
+    metric: Metric,
+    /// Maximum number of boxes displaced from a configuration of the solution.
+    vicinity_boxes: usize,
+    /// Maximum Manhattan distance of a displaced box from its position in the solution.
+    vicinity_radius: i32,
+    /// Maximum number of nodes generated by the search.
+    max_nodes: usize,
+}
+
+impl Optimizer {
+    /// Creates a new optimizer for the map.
+    pub fn new(map: Map, metric: Metric) -> Self {
+        let floor = FloorIndex::new(&map);
+        Self {
+            map,
+            floor,
+            metric,
+            vicinity_boxes: 2,
+            vicinity_radius: 2,
+            max_nodes: 1_000_000,
+        }
+    }
+
+    /// Returns the metric minimized by the optimizer.
+    pub fn metric(&self) -> Metric {
+        self.metric
+    }
+
+    /// Returns a better solution under the metric, or `None` if no better
+    /// solution is found in the vicinity of the solution.
+    ///
+    /// The returned solution has been verified by replaying it on a board.
+    pub fn optimize(&self, solution: &Actions, timeout: Duration) -> Option<Actions> {
+        let metrics = Metrics::new(&self.map, solution)?;
+        let configurations = self.configurations(solution);
+        let optimized = self.search(&configurations, timeout)?;
+        let optimized_metrics = Metrics::new(&self.map, &optimized)?;
+        (optimized_metrics.key(self.metric) < metrics.key(self.metric)).then_some(optimized)
+    }
+
+    /// Returns the box configurations after each push of the solution.
+    fn configurations(&self, solution: &Actions) -> Vec<BoxSet> {
+        let mut board = Board::with_map(self.map.clone());
+        let mut configurations = vec![BoxSet::from_positions(
+            &self.floor,
+            board.map.box_positions(),
+        )];
+        for action in &**solution {
+            board.do_action(action.direction());
+            if action.is_push() {
+                configurations.push(BoxSet::from_positions(
+                    &self.floor,
+                    board.map.box_positions(),
+                ));
+            }
+        }
+        configurations
+    }
+
+    /// Checks if the boxes are in the vicinity of a configuration of the solution.
+    fn is_in_vicinity(&self, boxes: &BoxSet, configurations: &[BoxSet]) -> bool {
+        configurations.iter().any(|configuration| {
+            boxes.difference_count(configuration) <= self.vicinity_boxes
+                && boxes.difference(configuration).all(|index| {
+                    let position = self.floor.position(index);
+                    configuration.difference(boxes).any(|other| {
+                        let other = self.floor.position(other);
+                        (position.x - other.x).abs() + (position.y - other.y).abs()
+                            <= self.vicinity_radius
+                    })
+                })
+        })
+    }
+
+    /// Finds the best solution in the vicinity of the configurations with Dijkstra's algorithm.
+    fn search(&self, configurations: &[BoxSet], timeout: Duration) -> Option<Actions> {
+        let timer = Instant::now();
+        let goals = BoxSet::from_positions(&self.floor, self.map.goal_positions());
+        // The direction of the last push only affects box lines and box changes.
+        let tracks_direction = matches!(self.metric, Metric::BoxLines | Metric::BoxChanges);
+
+        let mut nodes = vec![SearchNode {
+            boxes: configurations[0].clone(),
+            player_position: self.map.player_position(),
+            last_direction: None,
+            metrics: Metrics::default(),
+            parent: None,
+            push: None,
+        }];
+        let mut costs = HashMap::new();
+        let mut vicinity = HashMap::new();
+        let mut heap = BinaryHeap::from([Reverse((nodes[0].metrics.key(self.metric), 0))]);
+        while let Some(Reverse((cost, id))) = heap.pop() {
+            if timer.elapsed() >= timeout || nodes.len() > self.max_nodes {
+                return None;
+            }
+            let node = &nodes[id];
+            let key = (
+                node.boxes.clone(),
+                node.player_position,
+                node.last_direction,
+            );
+            if costs.get(&key).is_some_and(|&best| best < cost) {
+                continue;
+            }
+            if node.boxes.difference_count(&goals) == 0 {
+                return self.actions(&nodes, id);
+            }
+
+            let distances = self.player_distances(node);
+            let mut successors = Vec::new();
+            for box_index in node.boxes.iter() {
+                let box_position = self.floor.position(box_index);
+                for direction in Direction::iter() {
+                    let Some(&distance) = distances.get(&(box_position - &direction.into())) else {
+                        continue;
+                    };
+                    let new_box_position = box_position + &direction.into();
+                    let Some(new_box_index) = self.floor.index(new_box_position) else {
+                        continue;
+                    };
+                    if node.boxes.contains(new_box_index) {
+                        continue;
+                    }
+                    let mut new_boxes = node.boxes.clone();
+                    new_boxes.remove(box_index);
+                    new_boxes.insert(new_box_index);
+                    if !*vicinity
+                        .entry(new_boxes.clone())
+                        .or_insert_with(|| self.is_in_vicinity(&new_boxes, configurations))
+                    {
+                        continue;
+                    }
+
+                    let mut metrics = node.metrics;
+                    metrics.moves += distance + 1;
+                    metrics.pushes += 1;
+                    if distance != 0 || node.last_direction != Some(direction) {
+                        metrics.box_lines += 1;
+                    }
+                    let last_pushed_box = node
+                        .last_direction
+                        .map(|last_direction| node.player_position + &last_direction.into());
+                    if last_pushed_box != Some(box_position) {
+                        metrics.box_changes += 1;
+                    }
+                    successors.push(SearchNode {
+                        boxes: new_boxes,
+                        player_position: box_position,
+                        last_direction: Some(direction),
+                        metrics,
+                        parent: Some(id),
+                        push: Some(Push {
+                            box_position,
+                            direction,
+                            count: 1,
+                            goal_macro: None,
+                        }),
+                    });
+                }
+            }
+
+            for successor in successors {
+                let cost = successor.metrics.key(self.metric);
+                let key = (
+                    successor.boxes.clone(),
+                    successor.player_position,
+                    successor.last_direction.filter(|_| tracks_direction),
+                );
+                if costs.get(&key).is_some_and(|&best| best <= cost) {
+                    continue;
+                }
+                costs.insert(key, cost);
+                heap.push(Reverse((cost, nodes.len())));
+                nodes.push(SearchNode {
+                    last_direction: successor.last_direction.filter(|_| tracks_direction),
+                    ..successor
+                });
+            }
+        }
+        None
+    }
+
+    /// Returns the number of moves needed by the player to reach each square.
+    fn player_distances(&self, node: &SearchNode) -> HashMap<Vector2<i32>, usize> {
+        let mut distances = HashMap::from([(node.player_position, 0)]);
+        let mut queue = VecDeque::from([node.player_position]);
+        while let Some(position) = queue.pop_front() {
+            let distance = distances[&position];
+            for direction in Direction::iter() {
+                let next_position = position + &direction.into();
+                if self.map[next_position].intersects(Tiles::Wall)
+                    || self
+                        .floor
+                        .index(next_position)
+                        .is_some_and(|index| node.boxes.contains(index))
+                    || distances.contains_key(&next_position)
+                {
+                    continue;
+                }
+                distances.insert(next_position, distance + 1);
+                queue.push_back(next_position);
+            }
+        }
+        distances
+    }
+
+    /// Reconstructs the actions leading to the node.
+    fn actions(&self, nodes: &[SearchNode], id: usize) -> Option<Actions> {
+        let mut pushes = Vec::new();
+        let mut id = Some(id);
+        while let Some(current) = id {
+            pushes.extend(nodes[current].push);
+            id = nodes[current].parent;
+        }
+
+        let mut player_position = self.map.player_position();
+        let mut box_positions = self.map.box_positions().clone();
+        let mut actions = Actions::new();
+        for push in pushes.into_iter().rev() {
+            let push_position = push.box_position - &push.direction.into();
+            let path = find_path(player_position, push_position, |position| {
+                !self.map[position].intersects(Tiles::Wall) && !box_positions.contains(&position)
+            })?;
+            actions.extend(
+                path.windows(2)
+                    .map(|pos| Direction::try_from(pos[1] - pos[0]).unwrap())
+                    .map(Action::Move),
+            );
+            actions.push(Action::Push(push.direction));
+            box_positions.remove(&push.box_position);
+            box_positions.insert(push.destination());
+            player_position = push.box_position;
+        }
+        Some(actions)
+    }
+}
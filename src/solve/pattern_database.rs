@@ -0,0 +1,138 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+    str::FromStr,
+};
+
+use nalgebra::Vector2;
+use soukoban::{Map, Tiles, path_finding::compute_reachable_area};
+
+use crate::solve::encoding::*;
+
+/// A set of boxes which can not be pushed to goals while the player is in the
+/// area reachable from `player_position`.
+///
+/// Additional boxes only restrict the player further, so every state
+/// containing the boxes of a pattern, with the player in its area, is a
+/// deadlock.
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub struct DeadlockPattern {
+    boxes: Vec<Vector2<i32>>,
+    player_position: Vector2<i32>,
+}
+
+impl DeadlockPattern {
+    pub fn new(mut boxes: Vec<Vector2<i32>>, player_position: Vector2<i32>) -> Self {
+        boxes.sort_by_key(|position| (position.y, position.x));
+        Self {
+            boxes,
+            player_position,
+        }
+    }
+}
+
+/// Formats the pattern as `x,y x,y ...|x,y`, the boxes followed by the player.
+impl fmt::Display for DeadlockPattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (index, position) in self.boxes.iter().enumerate() {
+            if index > 0 {
+                write!(f, " ")?;
+            }
+            write!(f, "{},{}", position.x, position.y)?;
+        }
+        write!(f, "|{},{}", self.player_position.x, self.player_position.y)
+    }
+}
+
+impl FromStr for DeadlockPattern {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parse_position = |s: &str| {
+            let (x, y) = s
+                .split_once(',')
+                .ok_or_else(|| format!("invalid position: {s}"))?;
+            let x = x.parse().map_err(|_| format!("invalid position: {s}"))?;
+            let y = y.parse().map_err(|_| format!("invalid position: {s}"))?;
+            Ok::<_, String>(Vector2::new(x, y))
+        };
+        let (boxes, player_position) = s
+            .split_once('|')
+            .ok_or_else(|| format!("invalid deadlock pattern: {s}"))?;
+        let boxes = boxes
+            .split_whitespace()
+            .map(parse_position)
+            .collect::<Result<_, _>>()?;
+        Ok(Self::new(boxes, parse_position(player_position)?))
+    }
+}
+
+/// Deadlock patterns proven during the search.
+#[derive(Default)]
+pub struct PatternDatabase {
+    patterns: Vec<DeadlockPattern>,
+    /// Floor indices of the boxes of each pattern.
+    boxes: Vec<Vec<usize>>,
+    /// Squares of the player area of each pattern.
+    player_areas: Vec<BoxSet>,
+    /// Maps the floor index of a box to the patterns containing it.
+    patterns_by_box: HashMap<usize, Vec<usize>>,
+    known: HashSet<DeadlockPattern>,
+}
+
+impl PatternDatabase {
+    /// Adds a pattern to the database. Patterns which do not fit the map are ignored.
+    pub fn insert(&mut self, pattern: DeadlockPattern, map: &Map, floor: &FloorIndex) {
+        if self.known.contains(&pattern) {
+            return;
+        }
+        let Some(boxes) = pattern
+            .boxes
+            .iter()
+            .map(|position| floor.index(*position))
+            .collect::<Option<Vec<_>>>()
+        else {
+            return;
+        };
+        if floor.index(pattern.player_position).is_none() {
+            return;
+        }
+
+        let player_area = compute_reachable_area(pattern.player_position, |position| {
+            !map[position].intersects(Tiles::Wall) && !pattern.boxes.contains(&position)
+        });
+        let player_area = BoxSet::from_positions(floor, &player_area);
+
+        let id = self.patterns.len();
+        for index in &boxes {
+            self.patterns_by_box.entry(*index).or_default().push(id);
+        }
+        self.known.insert(pattern.clone());
+        self.patterns.push(pattern);
+        self.boxes.push(boxes);
+        self.player_areas.push(player_area);
+    }
+
+    /// Checks if a state matches a pattern containing the box which has just been moved.
+    ///
+    /// `has_box` checks if there is a box on the floor square with the index.
+    pub fn is_deadlocked(
+        &self,
+        has_box: impl Fn(usize) -> bool,
+        moved_box: usize,
+        player: usize,
+    ) -> bool {
+        let Some(ids) = self.patterns_by_box.get(&moved_box) else {
+            return false;
+        };
+        ids.iter().any(|&id| {
+            self.player_areas[id].contains(player)
+                && self.boxes[id].iter().all(|&index| has_box(index))
+        })
+    }
+
+    /// Returns all patterns in the database.
+    pub fn patterns(&self) -> &[DeadlockPattern] {
+        &self.patterns
+    }
+}
@@ -0,0 +1,148 @@
+use std::collections::VecDeque;
+
+use nalgebra::Vector2;
+use soukoban::{Map, Tiles, direction::Direction, path_finding::compute_reachable_area};
+
+use crate::solve::encoding::FloorIndex;
+
+/// Minimum number of pushes needed to move a single box to each goal.
+///
+/// The distances are exact when there are no other boxes on the map: the
+/// player has to walk around the box to reach the side it pushes from.
+pub struct PushDistances {
+    floor: FloorIndex,
+    /// `distances[goal][index * 4 + side]` is the distance of a box on the
+    /// floor square with the index, with the player on the side of the box.
+    distances: Vec<Vec<usize>>,
+}
+
+impl PushDistances {
+    /// Calculates the push distances by pulling a box away from each goal.
+    pub fn new(map: &Map) -> Self {
+        let floor = FloorIndex::new(map);
+        let components = side_components(map, &floor);
+
+        let mut distances = Vec::new();
+        for goal_position in map.goal_positions() {
+            let mut goal_distances = vec![usize::MAX; floor.count() * 4];
+            let mut queue = VecDeque::new();
+            let goal_index = floor.index(*goal_position).unwrap();
+            for side in Direction::iter() {
+                if components[goal_index][side_index(side)] != u8::MAX {
+                    goal_distances[goal_index * 4 + side_index(side)] = 0;
+                    queue.push_back((*goal_position, side));
+                }
+            }
+
+            while let Some((box_position, side)) = queue.pop_front() {
+                let box_index = floor.index(box_position).unwrap();
+                let distance = goal_distances[box_index * 4 + side_index(side)];
+                for pull_direction in Direction::iter() {
+                    // The player must be able to walk to the pulling side.
+                    if components[box_index][side_index(pull_direction)]
+                        != components[box_index][side_index(side)]
+                    {
+                        continue;
+                    }
+                    let next_box_position = box_position + &pull_direction.into();
+                    let next_player_position = next_box_position + &pull_direction.into();
+                    if !map.in_bounds(next_player_position)
+                        || map[next_player_position].intersects(Tiles::Wall)
+                    {
+                        continue;
+                    }
+                    let Some(next_box_index) = floor.index(next_box_position) else {
+                        continue;
+                    };
+                    let next_distance =
+                        &mut goal_distances[next_box_index * 4 + side_index(pull_direction)];
+                    if *next_distance != usize::MAX {
+                        continue;
+                    }
+                    *next_distance = distance + 1;
+                    queue.push_back((next_box_position, pull_direction));
+                }
+            }
+            distances.push(goal_distances);
+        }
+        Self { floor, distances }
+    }
+
+    /// Returns the number of goals.
+    pub fn goal_count(&self) -> usize {
+        self.distances.len()
+    }
+
+    /// Returns the minimum number of pushes needed to move the box to the goal
+    /// when the player is next to the box on the given side.
+    pub fn distance(
+        &self,
+        goal: usize,
+        box_position: Vector2<i32>,
+        player_side: Direction,
+    ) -> Option<usize> {
+        let index = self.floor.index(box_position)?;
+        match self.distances[goal][index * 4 + side_index(player_side)] {
+            usize::MAX => None,
+            distance => Some(distance),
+        }
+    }
+
+    /// Returns the minimum number of pushes needed to move the box to the goal
+    /// from any side.
+    pub fn box_distance(&self, goal: usize, box_position: Vector2<i32>) -> Option<usize> {
+        Direction::iter()
+            .filter_map(|side| self.distance(goal, box_position, side))
+            .min()
+    }
+
+    /// Returns the minimum number of pushes needed to move the box to any goal.
+    pub fn nearest_goal_distance(&self, box_position: Vector2<i32>) -> Option<usize> {
+        (0..self.goal_count())
+            .filter_map(|goal| self.box_distance(goal, box_position))
+            .min()
+    }
+
+    /// Checks if a box on the position can never be pushed to any goal.
+    pub fn is_dead_square(&self, box_position: Vector2<i32>) -> bool {
+        self.nearest_goal_distance(box_position).is_none()
+    }
+}
+
+/// Labels the sides of every floor square by the area the player can reach
+/// when a box is on the square. `u8::MAX` marks sides blocked by a wall.
+fn side_components(map: &Map, floor: &FloorIndex) -> Vec<[u8; 4]> {
+    let mut components = vec![[u8::MAX; 4]; floor.count()];
+    for (index, sides) in components.iter_mut().enumerate() {
+        let box_position = floor.position(index);
+        let mut label = 0;
+        for side in Direction::iter() {
+            let player_position = box_position + &side.into();
+            if sides[side_index(side)] != u8::MAX
+                || !map.in_bounds(player_position)
+                || map[player_position].intersects(Tiles::Wall)
+            {
+                continue;
+            }
+            let reachable_area = compute_reachable_area(player_position, |position| {
+                !map[position].intersects(Tiles::Wall) && position != box_position
+            });
+            for other_side in Direction::iter() {
+                if reachable_area.contains(&(box_position + &other_side.into())) {
+                    sides[side_index(other_side)] = label;
+                }
+            }
+            label += 1;
+        }
+    }
+    components
+}
+
+fn side_index(direction: Direction) -> usize {
+    match direction {
+        Direction::Up => 0,
+        Direction::Right => 1,
+        Direction::Down => 2,
+        Direction::Left => 3,
+    }
+}
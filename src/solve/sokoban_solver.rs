@@ -0,0 +1,53 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, RwLock},
+    time::Duration,
+};
+
+use nalgebra::Vector2;
+use soukoban::Actions;
+
+use crate::solve::{pattern_database::PatternDatabase, solver::SolveError, stats::SolverStats};
+
+/// A solver which searches for a solution in steps, so that it can be driven
+/// by a worker thread and cancelled between two steps.
+pub trait SokobanSolver: Send {
+    /// Starts the search.
+    fn start(&mut self);
+
+    /// Continues the search for at most `budget`.
+    ///
+    /// Returns `Err(SolveError::Timeout)` if the search has not finished yet,
+    /// or if it has run out of time, in which case [`Self::result`] is set.
+    fn step(&mut self, budget: Duration) -> Result<Actions, SolveError>;
+
+    /// Stops the search, later steps do nothing.
+    fn cancel(&mut self);
+
+    /// Returns the current progress of the search.
+    fn stats(&self) -> SolverStats;
+
+    /// Returns the result of the search, `None` if it has not finished.
+    fn result(&self) -> Option<Result<Actions, SolveError>>;
+
+    /// Returns the best solution if it has been found since the last call,
+    /// for solvers which keep improving their solution.
+    fn improved_solution(&mut self) -> Option<Actions> {
+        None
+    }
+
+    /// Returns the actions leading to the best state found so far.
+    fn best_actions(&self) -> Option<Actions> {
+        None
+    }
+
+    /// Returns the lower bound of the number of pushes or moves for each position.
+    fn lower_bounds(&self) -> HashMap<Vector2<i32>, usize> {
+        HashMap::new()
+    }
+
+    /// Returns the deadlock patterns proven by the search.
+    fn deadlock_patterns(&self) -> Option<Arc<RwLock<PatternDatabase>>> {
+        None
+    }
+}
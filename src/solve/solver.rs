@@ -1,15 +1,30 @@
 use std::{
-    cell::OnceCell,
     collections::{BinaryHeap, HashMap, HashSet},
+    mem,
+    sync::{
+        Arc, Mutex, OnceLock, RwLock,
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        mpsc::{self, Receiver, Sender},
+    },
+    thread,
     time::{Duration, Instant},
 };
 
-use crate::{box_pushable_paths_with_positions, solve::state::*};
+use crate::{
+    board::Board,
+    solve::{
+        arena::*, bidirectional::*, corral::*, decomposition::*, encoding::*, feature_space::*,
+        goal_room::*, pattern_database::*, push_distance::*, sokoban_solver::*, state::*, stats::*,
+        transposition_table::*, tunnel::*,
+    },
+    utils::box_pushable_paths_with_positions,
+};
 
-use itertools::Itertools;
 use nalgebra::Vector2;
 use serde::{Deserialize, Serialize};
-use soukoban::{Actions, Map, Tiles, direction::Direction, path_finding::compute_reachable_area};
+use soukoban::{
+    Action, Actions, Map, Tiles, deadlock, direction::Direction, path_finding::find_path,
+};
 
 #[derive(Clone, Copy, PartialEq, Eq, Debug, Default, Serialize, Deserialize)]
 pub enum Strategy {
@@ -25,6 +40,28 @@ pub enum Strategy {
 
     /// Find push optimal solutions with best moves
     OptimalPushMove,
+
+    /// Find push optimal solutions using little memory
+    IterativeDeepening,
+
+    /// Festival style feature space search for hard levels
+    FeatureSpace,
+
+    /// Speed priority with bounded memory, may miss solutions
+    Beam,
+
+    /// Weighted A*, the lower bound is multiplied by `weight`
+    Weighted { weight: u32 },
+}
+
+impl Strategy {
+    /// Returns `true` if the strategy guarantees optimal solutions.
+    pub fn is_optimal(self) -> bool {
+        matches!(
+            self,
+            Strategy::OptimalMovePush | Strategy::OptimalPushMove | Strategy::IterativeDeepening
+        )
+    }
 }
 
 #[derive(Clone, Copy, PartialEq, Eq, Debug, Default, Serialize, Deserialize)]
@@ -38,134 +75,1208 @@ pub enum LowerBoundMethod {
 
     /// Manhattan distance to nearest goal
     ManhattanDistance,
+
+    /// Minimum push count of a minimum-cost assignment between boxes and goals
+    MinimumMatching,
 }
 
 pub struct Solver {
     pub map: Map,
     strategy: Strategy,
     lower_bound_method: LowerBoundMethod,
-    lower_bounds: OnceCell<HashMap<Vector2<i32>, usize>>,
-    tunnels: OnceCell<HashSet<(Vector2<i32>, Direction)>>,
-    visited: HashSet<u64>,
+    lower_bounds: OnceLock<HashMap<Vector2<i32>, usize>>,
+    push_distances: OnceLock<PushDistances>,
+    goal_distances: OnceLock<Vec<Vec<usize>>>,
+    tunnels: OnceLock<Tunnels>,
+    goal_room: OnceLock<Option<GoalRoom>>,
+    floor: FloorIndex,
+    zobrist: Zobrist,
+    visited: HashSet<StateKey>,
     heap: BinaryHeap<State>,
+    /// Expanded nodes, one arena per thread.
+    arenas: Vec<Arena>,
+
+    corral_pruning: bool,
+    bidirectional: bool,
+    decomposition: bool,
+    anytime: bool,
+    pruning_counters: PruningCounters,
+    search_counters: SearchCounters,
+    /// Time spent searching.
+    elapsed: Duration,
+    deadlock_patterns: Arc<RwLock<PatternDatabase>>,
+
+    threads: usize,
+    /// Maximum memory used by the search, in bytes.
+    memory_limit: Option<usize>,
+    /// Per-thread open lists used by the parallel search.
+    open_lists: Vec<BinaryHeap<State>>,
+    /// Visited states shared by all threads, sharded by hash to reduce lock contention.
+    /// Maps the normalized key of a state to the lowest heuristic it was reached with.
+    shared_visited: Vec<Mutex<HashMap<StateKey, usize>>>,
+    /// Best solution found so far by the parallel search.
+    incumbent: Option<State>,
+    /// Progress of the iterative deepening search.
+    iterative_deepening: Option<IterativeDeepening>,
+    /// Progress of the bidirectional search.
+    bidirectional_search: Option<BidirectionalSearch>,
+    /// Progress of the feature space search.
+    feature_space: Option<FeatureSpace>,
+    /// Progress of the search over the parts of the level.
+    decomposed_search: Option<DecomposedSearch>,
+    /// Progress of the anytime search.
+    anytime_search: Option<AnytimeSearch>,
+
+    /// Result of the search once it has finished.
+    result: Option<Result<Actions>>,
+    /// Whether the search has been cancelled.
+    cancelled: bool,
 }
 
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub enum SolveError {
     Timeout,
     NoSolution,
+    OutOfMemory,
+    /// The external solver could not be started.
+    SpawnFailed,
 }
 
 type Result<T> = std::result::Result<T, SolveError>;
 
+/// Maximum number of states in the open list of the beam search.
+const BEAM_WIDTH: usize = 200_000;
+
+/// Number of expanded states between two checks of the memory usage.
+const MEMORY_CHECK_INTERVAL: usize = 4096;
+
+/// Number of slots of the transposition table used by the iterative deepening search.
+const TRANSPOSITION_TABLE_SIZE: usize = 1 << 18;
+
+/// State of an iterative deepening search, kept between calls.
+struct IterativeDeepening {
+    root: State,
+    /// Maximum estimated number of pushes of the states searched by the current iteration.
+    threshold: usize,
+    /// Minimum estimated number of pushes of the states exceeding the threshold.
+    next_threshold: usize,
+    /// Unsearched states of each depth of the current path.
+    stack: Vec<Vec<State>>,
+    table: TranspositionTable,
+}
+
+/// State of an anytime search, kept between calls.
+struct AnytimeSearch {
+    /// Weight of the lower bound in the current iteration.
+    weight: usize,
+    /// Lowest number of moves each state has been reached with in the current
+    /// iteration, keyed by the exact player position.
+    visited: HashMap<StateKey, usize>,
+    /// Best solution found so far.
+    solution: Option<Actions>,
+    /// Whether the best solution has not been returned by `improved_solution` yet.
+    improved: bool,
+}
+
+/// State shared by the threads of a parallel search.
+struct ParallelSearch {
+    /// Number of states in open lists or in flight between threads.
+    pending: AtomicUsize,
+    stop: AtomicBool,
+    /// Whether the search has been stopped by the memory limit.
+    out_of_memory: AtomicBool,
+    /// Heuristic value of the incumbent solution.
+    bound: AtomicUsize,
+    incumbent: Mutex<Option<State>>,
+    senders: Vec<Sender<State>>,
+    timeout: Duration,
+    timer: Instant,
+}
+
 impl Solver {
     /// Creates a new solver.
     pub fn new(map: Map, strategy: Strategy, lower_bound_method: LowerBoundMethod) -> Self {
+        let floor = FloorIndex::new(&map);
+        let zobrist = Zobrist::new(&floor, map.dimensions());
         let mut instance = Self {
             map,
             strategy,
             lower_bound_method,
-            lower_bounds: OnceCell::new(),
-            tunnels: OnceCell::new(),
+            lower_bounds: OnceLock::new(),
+            push_distances: OnceLock::new(),
+            goal_distances: OnceLock::new(),
+            tunnels: OnceLock::new(),
+            goal_room: OnceLock::new(),
+            floor,
+            zobrist,
             visited: HashSet::new(),
             heap: BinaryHeap::new(),
+            arenas: vec![Arena::new(0)],
+            corral_pruning: true,
+            bidirectional: false,
+            decomposition: false,
+            anytime: false,
+            pruning_counters: PruningCounters::default(),
+            search_counters: SearchCounters::default(),
+            elapsed: Duration::ZERO,
+            deadlock_patterns: Arc::default(),
+            threads: 1,
+            memory_limit: None,
+            open_lists: Vec::new(),
+            shared_visited: Vec::new(),
+            incumbent: None,
+            iterative_deepening: None,
+            bidirectional_search: None,
+            feature_space: None,
+            decomposed_search: None,
+            anytime_search: None,
+            result: None,
+            cancelled: false,
         };
-        instance.heap.push(State::new(
-            instance.map.player_position(),
-            instance.map.box_positions().clone(),
-            Actions::new(),
-            &instance,
-        ));
+        instance.heap.push(instance.initial_state());
         instance
     }
 
-    /// Searches for solution using the A* algorithm.
+    /// Returns the initial state of the search.
+    fn initial_state(&self) -> State {
+        let boxes = BoxSet::from_positions(&self.floor, self.map.box_positions());
+        let boxes_hash = self.zobrist.boxes_hash(&boxes);
+        State::new(self.map.player_position(), boxes, boxes_hash, self)
+    }
+
+    /// Searches for solution with the search algorithm selected by the strategy and options.
     pub fn search(&mut self, timeout: Duration) -> Result<Actions> {
+        let timer = Instant::now();
+        let result = if let Some(result) = self.decomposed_search(timeout) {
+            result
+        } else {
+            let timeout = timeout.saturating_sub(timer.elapsed());
+            if self.strategy == Strategy::IterativeDeepening {
+                self.iterative_deepening_search(timeout)
+            } else if self.strategy == Strategy::FeatureSpace {
+                self.feature_space_search(timeout)
+            } else if self.is_anytime() {
+                self.anytime_search(timeout)
+            } else if self.threads > 1 && self.strategy != Strategy::Beam {
+                self.parallel_search(timeout)
+            } else if self.bidirectional && !self.strategy.is_optimal() {
+                self.bidirectional_search(timeout)
+            } else {
+                self.best_first_search(timeout)
+            }
+        };
+        self.elapsed += timer.elapsed();
+        result
+    }
+
+    /// Searches for solution by solving the independent parts of the level
+    /// one after another.
+    ///
+    /// Returns `None` if the level can not be decomposed, or its parts can not
+    /// be solved one after another. The whole level is searched instead.
+    fn decomposed_search(&mut self, timeout: Duration) -> Option<Result<Actions>> {
+        if !self.decomposition || self.strategy.is_optimal() || self.is_anytime() {
+            return None;
+        }
+        let Some(mut search) = self
+            .decomposed_search
+            .take()
+            .or_else(|| DecomposedSearch::new(&self.map))
+        else {
+            self.decomposition = false;
+            return None;
+        };
+        let result = search.search(timeout, |map| self.part_solver(map));
+        // Kept for its stats.
+        self.decomposed_search = Some(search);
+        if result == Err(SolveError::NoSolution) {
+            self.decomposition = false;
+            return None;
+        }
+        Some(result)
+    }
+
+    /// Creates a solver of a part of the level, with the same options.
+    fn part_solver(&self, map: Map) -> Solver {
+        let mut solver = Solver::new(map, self.strategy, self.lower_bound_method);
+        solver.threads = self.threads;
+        solver.memory_limit = self.memory_limit;
+        solver.corral_pruning = self.corral_pruning;
+        solver.bidirectional = self.bidirectional;
+        solver
+    }
+
+    /// Searches for solution using the A* algorithm.
+    fn best_first_search(&mut self, timeout: Duration) -> Result<Actions> {
         let timer = Instant::now();
         self.visited
-            .insert(self.heap.peek().unwrap().normalized_hash(self));
+            .insert(self.heap.peek().unwrap().normalized_key(self));
+        let mut expansions = 0;
         while let Some(state) = self.heap.pop() {
             if timer.elapsed() >= timeout {
+                self.heap.push(state);
                 return Err(SolveError::Timeout);
             }
             if state.is_solved(self) {
-                return Ok(state.actions);
+                return Ok(state.actions(self));
             }
 
-            for successor in state.successors(self) {
-                if !self.visited.insert(successor.normalized_hash(self)) {
+            let node = self.arenas[0].insert(state.node());
+            for successor in state.successors(node, self) {
+                if !self.visited.insert(successor.normalized_key(self)) {
+                    self.record_duplicate();
                     continue;
                 }
                 self.heap.push(successor);
             }
 
-            // Solver::shrink_heap(&mut self.heap);
+            expansions += 1;
+            if expansions % MEMORY_CHECK_INTERVAL == 0 {
+                self.limit_memory(0)?;
+            }
         }
 
         Err(SolveError::NoSolution)
     }
 
-    pub fn strategy(&self) -> Strategy {
-        self.strategy
+    /// Searches for solutions with weighted A*, improving the solution with a
+    /// lower weight each time one is found.
+    ///
+    /// States which can not lead to a solution with fewer moves than the best
+    /// one are pruned, and states reached with fewer moves are searched again.
+    /// States are compared with the exact player position rather than the
+    /// normalized one, since the moves of a state depend on it. The best
+    /// solution is returned once it is proven move optimal: either it is found
+    /// with a weight of at most 1, or no state is left to search.
+    fn anytime_search(&mut self, timeout: Duration) -> Result<Actions> {
+        let timer = Instant::now();
+        if self.anytime_search.is_none() {
+            let root = self.heap.peek().unwrap();
+            self.anytime_search = Some(AnytimeSearch {
+                weight: self.heuristic_weight(),
+                visited: HashMap::from([(root.exact_key(self), root.moves)]),
+                solution: None,
+                improved: false,
+            });
+        }
+        let mut expansions = 0;
+        while let Some(state) = self.heap.pop() {
+            if timer.elapsed() >= timeout {
+                self.heap.push(state);
+                return Err(SolveError::Timeout);
+            }
+            if state.is_solved(self) {
+                // The move bound only lets better solutions through.
+                let solution = state.actions(self);
+                let search = self.anytime_search.as_mut().unwrap();
+                search.solution = Some(solution);
+                search.improved = true;
+                if search.weight <= 1 {
+                    break;
+                }
+                self.restart_anytime_search();
+                continue;
+            }
+            // skip states which have been reached with fewer moves since
+            let key = state.exact_key(self);
+            if self.anytime_search.as_ref().unwrap().visited[&key] < state.moves {
+                continue;
+            }
+
+            let node = self.arenas[0].insert(state.node());
+            for successor in state.successors(node, self) {
+                let key = successor.exact_key(self);
+                let visited = &mut self.anytime_search.as_mut().unwrap().visited;
+                if visited
+                    .get(&key)
+                    .is_some_and(|&moves| moves <= successor.moves)
+                {
+                    self.record_duplicate();
+                    continue;
+                }
+                visited.insert(key, successor.moves);
+                self.heap.push(successor);
+            }
+
+            expansions += 1;
+            if expansions % MEMORY_CHECK_INTERVAL == 0
+                && let Err(error) = self.limit_memory(0)
+            {
+                // Keep the best solution found before running out of memory.
+                let search = self.anytime_search.as_ref().unwrap();
+                return search.solution.clone().ok_or(error);
+            }
+        }
+
+        self.anytime_search
+            .as_ref()
+            .unwrap()
+            .solution
+            .clone()
+            .ok_or(SolveError::NoSolution)
     }
 
-    /// Returns the best state in the binary heap, or `None` if it is empty.
-    pub fn best_state(&self) -> Option<&State> {
-        self.heap.peek()
+    /// Starts the next iteration of the anytime search with a lower weight.
+    fn restart_anytime_search(&mut self) {
+        let search = self.anytime_search.as_mut().unwrap();
+        search.weight = (search.weight / 2).max(1);
+        self.heap.clear();
+        self.arenas = vec![Arena::new(0)];
+        let root = self.initial_state();
+        let key = root.exact_key(self);
+        let search = self.anytime_search.as_mut().unwrap();
+        search.visited = HashMap::from([(key, root.moves)]);
+        self.heap.push(root);
     }
 
-    /// Returns a reference to the set of tunnels.
-    pub fn tunnels(&self) -> &HashSet<(Vector2<i32>, Direction)> {
-        self.tunnels.get_or_init(|| self.calculate_tunnels())
+    /// Searches for solution from both the initial state and the solved states.
+    ///
+    /// The forward search pushes boxes, the backward search pulls boxes away
+    /// from the goals. Both searches share a table of normalized states and
+    /// expand states alternately until a state is reached by both of them.
+    fn bidirectional_search(&mut self, timeout: Duration) -> Result<Actions> {
+        let timer = Instant::now();
+        let mut search = match self.bidirectional_search.take() {
+            Some(search) => search,
+            None => {
+                let mut search = BidirectionalSearch::new(self);
+                let root = self.heap.peek().unwrap();
+                search
+                    .table
+                    .insert(root.normalized_key(self), Visit::Forward(root.node()));
+                search
+            }
+        };
+        let result = self.meet(&mut search, timeout, timer);
+        self.bidirectional_search = Some(search);
+        result
     }
 
-    /// Calculates and returns the set of tunnels in the level.
-    fn calculate_tunnels(&self) -> HashSet<(Vector2<i32>, Direction)> {
-        let mut tunnels = HashSet::new();
-        for x in 1..self.map.dimensions().x - 1 {
-            for y in 1..self.map.dimensions().y - 1 {
-                let box_position = Vector2::new(x, y);
-                if !self.map[box_position].intersects(Tiles::Floor) {
-                    continue;
+    /// Continues both searches of a bidirectional search until they meet or
+    /// the timeout is reached.
+    fn meet(
+        &mut self,
+        search: &mut BidirectionalSearch,
+        timeout: Duration,
+        timer: Instant,
+    ) -> Result<Actions> {
+        let mut expansions = 0;
+        while let Some(state) = self.heap.pop() {
+            if timer.elapsed() >= timeout {
+                self.heap.push(state);
+                return Err(SolveError::Timeout);
+            }
+            if state.is_solved(self) {
+                return Ok(state.actions(self));
+            }
+
+            let node = self.arenas[0].insert(state.node());
+            for successor in state.successors(node, self) {
+                let key = successor.normalized_key(self);
+                match search.table.get(&key) {
+                    Some(Visit::Backward(backward)) => {
+                        if let Some(actions) =
+                            self.stitch(successor.node(), *backward, &search.arena)
+                        {
+                            return Ok(actions);
+                        }
+                    }
+                    Some(Visit::Forward(_)) => self.record_duplicate(),
+                    None => {
+                        search.table.insert(key, Visit::Forward(successor.node()));
+                        self.heap.push(successor);
+                    }
+                }
+            }
+
+            if let Some((forward, backward)) = search.step(self)
+                && let Some(actions) = self.stitch(forward, backward, &search.arena)
+            {
+                return Ok(actions);
+            }
+
+            expansions += 1;
+            if expansions % MEMORY_CHECK_INTERVAL == 0 {
+                self.limit_memory(search.memory_usage(self))?;
+            }
+        }
+
+        Err(SolveError::NoSolution)
+    }
+
+    /// Joins the pushes leading to the forward node with the pushes leading
+    /// from the backward node to a solved state.
+    ///
+    /// Returns `None` if the joined actions do not solve the level.
+    fn stitch(&self, forward: Node, backward: Node, backward_arena: &Arena) -> Option<Actions> {
+        let mut pushes = self.pushes(forward);
+        pushes.extend(backward.push);
+        let mut parent = backward.parent;
+        while let Some(id) = parent {
+            let node = backward_arena.get(id);
+            pushes.extend(node.push);
+            parent = node.parent;
+        }
+        let actions = self.pushes_to_actions(&pushes)?;
+
+        let mut board = Board::with_map(self.map.clone());
+        for action in &*actions {
+            board.do_action(action.direction());
+        }
+        board.is_solved().then(|| board.actions().clone())
+    }
+
+    /// Searches for solution using a Festival style feature space search.
+    ///
+    /// The search can be resumed after a timeout.
+    fn feature_space_search(&mut self, timeout: Duration) -> Result<Actions> {
+        let timer = Instant::now();
+        let mut space = match self.feature_space.take() {
+            Some(space) => space,
+            None => {
+                let root = self.heap.pop().ok_or(SolveError::NoSolution)?;
+                self.visited.insert(root.normalized_key(self));
+                FeatureSpace::new(root, self)
+            }
+        };
+        let mut expansions = 0;
+        let result = loop {
+            if timer.elapsed() >= timeout {
+                break Err(SolveError::Timeout);
+            }
+            expansions += 1;
+            if expansions % MEMORY_CHECK_INTERVAL == 0
+                && let Err(error) = self.limit_memory(space.memory_usage(self))
+            {
+                break Err(error);
+            }
+            let Some((state, weight, features)) = space.pop() else {
+                break Err(SolveError::NoSolution);
+            };
+            if state.is_solved(self) {
+                break Ok(state.actions(self));
+            }
+
+            let node = self.arenas[0].insert(state.node());
+            let successors = state
+                .successors(node, self)
+                .into_iter()
+                .filter(|successor| {
+                    let inserted = self.visited.insert(successor.normalized_key(self));
+                    if !inserted {
+                        self.record_duplicate();
+                    }
+                    inserted
+                })
+                .collect();
+            space.extend(weight, features, successors, self);
+        };
+        self.feature_space = Some(space);
+        result
+    }
+
+    /// Searches for solution using the iterative deepening A* algorithm.
+    ///
+    /// Each iteration is a depth first search of the states whose estimated
+    /// number of pushes does not exceed the threshold, so only the current
+    /// path is kept in memory. The search can be resumed after a timeout.
+    fn iterative_deepening_search(&mut self, timeout: Duration) -> Result<Actions> {
+        let timer = Instant::now();
+        let mut search = match self.iterative_deepening.take() {
+            Some(search) => search,
+            None => {
+                let root = self.heap.pop().ok_or(SolveError::NoSolution)?;
+                IterativeDeepening {
+                    threshold: root.heuristic(),
+                    next_threshold: usize::MAX,
+                    stack: vec![vec![root.clone()]],
+                    root,
+                    table: TranspositionTable::new(TRANSPOSITION_TABLE_SIZE),
                 }
+            }
+        };
+        let result = self.deepen(&mut search, timeout, timer);
+        self.iterative_deepening = Some(search);
+        result
+    }
+
+    /// Continues the iterations of an iterative deepening search until a
+    /// solution is found or the timeout is reached.
+    fn deepen(
+        &mut self,
+        search: &mut IterativeDeepening,
+        timeout: Duration,
+        timer: Instant,
+    ) -> Result<Actions> {
+        loop {
+            if timer.elapsed() >= timeout {
+                return Err(SolveError::Timeout);
+            }
+            let Some(states) = search.stack.last_mut() else {
+                // No state within the threshold leads to a solution, start the
+                // next iteration with the lowest estimate which exceeded it.
+                if search.next_threshold == usize::MAX {
+                    return Err(SolveError::NoSolution);
+                }
+                search.threshold = mem::replace(&mut search.next_threshold, usize::MAX);
+                search.stack.push(vec![search.root.clone()]);
+                continue;
+            };
+            let Some(state) = states.pop() else {
+                search.stack.pop();
+                continue;
+            };
+
+            if state.heuristic() > search.threshold {
+                search.next_threshold = search.next_threshold.min(state.heuristic());
+                continue;
+            }
+            if state.is_solved(self) {
+                return Ok(state.actions(self));
+            }
+            if !search
+                .table
+                .insert(state.normalized_key(self), search.threshold, state.pushes)
+            {
+                self.record_duplicate();
+                continue;
+            }
+
+            // The arena only contains the nodes of the current path.
+            self.arenas[0].truncate(search.stack.len() - 1);
+            let node = self.arenas[0].insert(state.node());
+            let mut successors = state.successors(node, self);
+            // Successors are popped from the end, so the most promising one is searched first.
+            successors.sort_unstable();
+            search.stack.push(successors);
+        }
+    }
+
+    /// Searches for solution using the hash distributed A* algorithm.
+    ///
+    /// Each thread owns an open list, successors are sent to the thread
+    /// selected by their normalized hash. The open lists are kept between
+    /// calls, so the search can be resumed after a timeout.
+    fn parallel_search(&mut self, timeout: Duration) -> Result<Actions> {
+        let timer = Instant::now();
+        if self.open_lists.is_empty() {
+            self.open_lists = (0..self.threads).map(|_| BinaryHeap::new()).collect();
+            for index in self.arenas.len()..self.threads {
+                self.arenas.push(Arena::new(index));
+            }
+            self.shared_visited = (0..self.threads * 16).map(|_| Mutex::default()).collect();
+            while let Some(state) = self.heap.pop() {
+                let key = state.normalized_key(self);
+                let owner = self.owner(&key);
+                let shard = self.shard(&key);
+                self.shared_visited[shard]
+                    .get_mut()
+                    .unwrap()
+                    .insert(key, state.heuristic());
+                self.open_lists[owner].push(state);
+            }
+        }
+
+        let open_lists = mem::take(&mut self.open_lists);
+        let arenas = mem::take(&mut self.arenas);
+        let (senders, receivers): (Vec<_>, Vec<_>) =
+            (0..self.threads).map(|_| mpsc::channel()).unzip();
+        let search = ParallelSearch {
+            pending: AtomicUsize::new(open_lists.iter().map(BinaryHeap::len).sum()),
+            stop: AtomicBool::new(false),
+            out_of_memory: AtomicBool::new(false),
+            bound: AtomicUsize::new(
+                self.incumbent
+                    .as_ref()
+                    .map_or(usize::MAX, |state| state.heuristic()),
+            ),
+            incumbent: Mutex::new(self.incumbent.take()),
+            senders,
+            timeout,
+            timer,
+        };
 
-                for (up, right, down, left) in [
-                    Direction::Up,
-                    Direction::Right,
-                    Direction::Down,
-                    Direction::Left,
-                    Direction::Up,
-                    Direction::Right,
-                    Direction::Down,
-                ]
+        let solver = &*self;
+        let results: Vec<_> = thread::scope(|scope| {
+            let handles: Vec<_> = open_lists
                 .into_iter()
-                .tuple_windows()
+                .zip(receivers)
+                .zip(arenas)
+                .enumerate()
+                .map(|(index, ((open_list, inbox), arena))| {
+                    let search = &search;
+                    scope.spawn(move || {
+                        solver.parallel_worker(index, open_list, inbox, arena, search)
+                    })
+                })
+                .collect();
+            handles
+                .into_iter()
+                .map(|handle| handle.join().unwrap())
+                .collect()
+        });
+
+        // Move the states which are still in flight back into the open lists.
+        for (mut open_list, inbox, arena) in results {
+            open_list.extend(inbox.try_iter());
+            self.open_lists.push(open_list);
+            self.arenas.push(arena);
+        }
+        self.incumbent = search.incumbent.into_inner().unwrap();
+
+        let exhausted = search.pending.load(Ordering::SeqCst) == 0;
+        if let Some(incumbent) = &self.incumbent
+            && (exhausted || !self.strategy.is_optimal())
+        {
+            return Ok(incumbent.actions(self));
+        }
+        if search.out_of_memory.load(Ordering::Relaxed) {
+            return Err(SolveError::OutOfMemory);
+        }
+        if exhausted {
+            return Err(SolveError::NoSolution);
+        }
+        Err(SolveError::Timeout)
+    }
+
+    /// Expands states of a single open list until the parallel search stops.
+    fn parallel_worker(
+        &self,
+        index: usize,
+        mut open_list: BinaryHeap<State>,
+        inbox: Receiver<State>,
+        mut arena: Arena,
+        search: &ParallelSearch,
+    ) -> (BinaryHeap<State>, Receiver<State>, Arena) {
+        let mut expansions = 0;
+        while !search.stop.load(Ordering::Relaxed) && search.timer.elapsed() < search.timeout {
+            open_list.extend(inbox.try_iter());
+            let Some(state) = open_list.pop() else {
+                if search.pending.load(Ordering::SeqCst) == 0 {
+                    break;
+                }
+                thread::yield_now();
+                continue;
+            };
+
+            // States which can not lead to a better solution than the incumbent are discarded.
+            if state.heuristic() >= search.bound.load(Ordering::Relaxed) {
+                search.pending.fetch_sub(1, Ordering::SeqCst);
+                continue;
+            }
+
+            if state.is_solved(self) {
+                let mut incumbent = search.incumbent.lock().unwrap();
+                if incumbent
+                    .as_ref()
+                    .is_none_or(|incumbent| state.heuristic() < incumbent.heuristic())
                 {
-                    let player_position = box_position + &down.into();
-
-                    //  .      .      .
-                    // #$# or #$_ or _$#
-                    // #@#    #@#    #@#
-                    if self.map[player_position + &left.into()].intersects(Tiles::Wall)
-                        && self.map[player_position + &right.into()].intersects(Tiles::Wall)
-                        && (self.map[box_position + &left.into()].intersects(Tiles::Wall)
-                            && self.map[box_position + &right.into()].intersects(Tiles::Wall)
-                            || self.map[box_position + &right.into()].intersects(Tiles::Wall)
-                                && self.map[box_position + &left.into()].intersects(Tiles::Floor)
-                            || self.map[box_position + &right.into()].intersects(Tiles::Floor)
-                                && self.map[box_position + &left.into()].intersects(Tiles::Wall))
-                        && self.map[box_position].intersects(Tiles::Floor)
-                        && self
-                            .lower_bounds()
-                            .contains_key(&(box_position + &up.into()))
-                        && !self.map[box_position].intersects(Tiles::Goal)
+                    search.bound.store(state.heuristic(), Ordering::Relaxed);
+                    *incumbent = Some(state);
+                }
+                if !self.strategy.is_optimal() {
+                    search.stop.store(true, Ordering::Relaxed);
+                }
+                search.pending.fetch_sub(1, Ordering::SeqCst);
+                continue;
+            }
+
+            let node = arena.insert(state.node());
+            for successor in state.successors(node, self) {
+                if successor.heuristic() >= search.bound.load(Ordering::Relaxed) {
+                    continue;
+                }
+                let key = successor.normalized_key(self);
+                let owner = self.owner(&key);
+                {
+                    let mut visited = self.shared_visited[self.shard(&key)].lock().unwrap();
+                    // Only reopen a state if it is reached with a lower cost.
+                    if visited
+                        .get(&key)
+                        .is_some_and(|&heuristic| heuristic <= successor.heuristic())
                     {
-                        tunnels.insert((player_position, up));
+                        self.record_duplicate();
+                        continue;
                     }
+                    visited.insert(key, successor.heuristic());
+                }
+                search.pending.fetch_add(1, Ordering::SeqCst);
+                if owner == index {
+                    open_list.push(successor);
+                } else {
+                    search.senders[owner].send(successor).unwrap();
                 }
             }
+            search.pending.fetch_sub(1, Ordering::SeqCst);
+
+            // The open lists and arenas of the threads are assumed to be of similar size.
+            expansions += 1;
+            if expansions % MEMORY_CHECK_INTERVAL == 0
+                && self.is_out_of_memory(
+                    (open_list.len() * self.state_size() + arena.memory_usage()) * self.threads,
+                )
+            {
+                search.out_of_memory.store(true, Ordering::Relaxed);
+                search.stop.store(true, Ordering::Relaxed);
+            }
+        }
+        (open_list, inbox, arena)
+    }
+
+    /// Returns the index of the thread that owns the state with the given key.
+    fn owner(&self, key: &StateKey) -> usize {
+        (key.zobrist_hash() % self.threads as u64) as usize
+    }
+
+    /// Returns the index of the visited shard containing the state with the given key.
+    fn shard(&self, key: &StateKey) -> usize {
+        ((key.zobrist_hash() >> 32) % self.shared_visited.len() as u64) as usize
+    }
+
+    pub fn strategy(&self) -> Strategy {
+        self.strategy
+    }
+
+    pub fn lower_bound_method(&self) -> LowerBoundMethod {
+        self.lower_bound_method
+    }
+
+    /// Sets the number of threads used by the search.
+    pub fn set_threads(&mut self, threads: usize) {
+        self.threads = threads.max(1);
+    }
+
+    /// Sets whether the search is pruned with corrals.
+    pub fn set_corral_pruning(&mut self, corral_pruning: bool) {
+        self.corral_pruning = corral_pruning;
+    }
+
+    pub fn corral_pruning(&self) -> bool {
+        self.corral_pruning
+    }
+
+    /// Sets whether a backward search over pulls is run alongside the search.
+    ///
+    /// Only used by single threaded searches with strategies which do not
+    /// guarantee optimal solutions.
+    pub fn set_bidirectional(&mut self, bidirectional: bool) {
+        self.bidirectional = bidirectional;
+    }
+
+    /// Sets whether the parts of the level whose boxes never interact are
+    /// solved separately.
+    ///
+    /// Only used by strategies which do not guarantee optimal solutions.
+    pub fn set_decomposition(&mut self, decomposition: bool) {
+        self.decomposition = decomposition;
+    }
+
+    /// Sets whether the search keeps improving its solution after the first
+    /// one, until it is proven move optimal.
+    ///
+    /// Only used by the `Weighted` strategy, with a single thread.
+    pub fn set_anytime(&mut self, anytime: bool) {
+        self.anytime = anytime;
+    }
+
+    /// Returns `true` if the search keeps improving its solution.
+    pub fn is_anytime(&self) -> bool {
+        self.anytime && matches!(self.strategy, Strategy::Weighted { .. })
+    }
+
+    /// Returns the weight of the lower bound in the heuristic of the
+    /// `Weighted` strategy, which decreases during an anytime search.
+    pub fn heuristic_weight(&self) -> usize {
+        match self.strategy {
+            Strategy::Weighted { weight } => self
+                .anytime_search
+                .as_ref()
+                .map_or(weight as usize, |search| search.weight),
+            _ => 1,
+        }
+    }
+
+    /// Returns the number of moves of the best solution found by the anytime
+    /// search, states which can not lead to fewer moves are pruned.
+    pub fn move_bound(&self) -> Option<usize> {
+        self.anytime_search
+            .as_ref()
+            .and_then(|search| search.solution.as_ref())
+            .map(Actions::moves)
+    }
+
+    /// Returns the best solution of the anytime search if it has been found
+    /// since the last call.
+    pub fn improved_solution(&mut self) -> Option<Actions> {
+        let search = self.anytime_search.as_mut()?;
+        if !mem::take(&mut search.improved) {
+            return None;
+        }
+        search.solution.clone()
+    }
+
+    /// Sets the maximum memory used by the search, in bytes.
+    ///
+    /// The memory usage is estimated, see [`SolverStats::memory_usage`].
+    pub fn set_memory_limit(&mut self, memory_limit: Option<usize>) {
+        self.memory_limit = memory_limit;
+    }
+
+    /// Adds deadlock patterns proven by a previous search.
+    pub fn add_deadlock_patterns(&mut self, patterns: impl IntoIterator<Item = DeadlockPattern>) {
+        let mut database = self.deadlock_patterns.write().unwrap();
+        for pattern in patterns {
+            database.insert(pattern, &self.map, &self.floor);
+        }
+    }
+
+    /// Returns the deadlock patterns of the solver, which are updated during the search.
+    pub fn deadlock_patterns(&self) -> Arc<RwLock<PatternDatabase>> {
+        self.deadlock_patterns.clone()
+    }
+
+    /// Adds a deadlock pattern proven during the search.
+    pub fn learn_deadlock_pattern(&self, pattern: DeadlockPattern) {
+        self.deadlock_patterns
+            .write()
+            .unwrap()
+            .insert(pattern, &self.map, &self.floor);
+    }
+
+    /// Checks if the boxes and the player match a deadlock pattern containing the moved box.
+    ///
+    /// `has_box` checks if there is a box on the floor square with the index.
+    pub fn matches_deadlock_pattern(
+        &self,
+        has_box: impl Fn(usize) -> bool,
+        moved_box: usize,
+        player_position: Vector2<i32>,
+    ) -> bool {
+        let player = self.floor.index(player_position).unwrap();
+        self.deadlock_patterns
+            .read()
+            .unwrap()
+            .is_deadlocked(has_box, moved_box, player)
+    }
+
+    /// Returns the boxes of a position of the level which are part of a deadlock.
+    ///
+    /// Detects boxes on dead squares and frozen boxes which are not on goals.
+    /// If `corral` is set and no such box is found, the corrals of the
+    /// position are also searched for deadlocks.
+    pub fn deadlocked_boxes(
+        &self,
+        player_position: Vector2<i32>,
+        box_positions: &HashSet<Vector2<i32>>,
+        corral: bool,
+    ) -> HashSet<Vector2<i32>> {
+        let mut deadlocked_boxes: HashSet<_> = box_positions
+            .iter()
+            .filter(|box_position| !self.map[**box_position].intersects(Tiles::Goal))
+            .filter(|box_position| {
+                self.push_distances().is_dead_square(**box_position)
+                    || deadlock::is_freeze_deadlock(
+                        &self.map,
+                        **box_position,
+                        box_positions,
+                        &mut HashSet::new(),
+                    )
+            })
+            .copied()
+            .collect();
+        if corral && deadlocked_boxes.is_empty() {
+            let boxes = BoxSet::from_positions(&self.floor, box_positions);
+            let boxes_hash = self.zobrist.boxes_hash(&boxes);
+            let state = State::new(player_position, boxes, boxes_hash, self);
+            let player_reachable_area = state.player_reachable_area(self);
+            for corral in Corral::find_all(&state, &player_reachable_area, self) {
+                if corral.deadlock_pattern(&state, self).is_some() {
+                    deadlocked_boxes.extend(corral.boxes());
+                }
+            }
+        }
+        deadlocked_boxes
+    }
+
+    /// Counts an expanded state, its successors and the stats collected while expanding it.
+    pub fn record_expansion(&self, successors: usize, stats: PruningStats) {
+        self.search_counters.add_expansion(successors);
+        self.pruning_counters.add(stats);
+    }
+
+    /// Counts a generated state which has already been visited.
+    pub fn record_duplicate(&self) {
+        self.search_counters.add_duplicate();
+    }
+
+    /// Returns the current progress of the search.
+    pub fn stats(&self) -> SolverStats {
+        let (expanded_nodes, generated_nodes, duplicates) = self.search_counters.snapshot();
+        let mut stats = SolverStats {
+            expanded_nodes,
+            generated_nodes,
+            duplicates,
+            pruning: self.pruning_stats(),
+            open_nodes: self.open_nodes(),
+            visited_nodes: self.visited_nodes(),
+            memory_usage: self.memory_usage(),
+            best_lower_bound: self.best_state().map(|state| state.lower_bound(self)),
+            elapsed: self.elapsed,
+        };
+        if let Some(search) = &self.decomposed_search {
+            search.add_stats(&mut stats);
+        }
+        stats
+    }
+
+    /// Returns the number of states waiting to be expanded.
+    fn open_nodes(&self) -> usize {
+        self.heap.len()
+            + self.open_lists.iter().map(BinaryHeap::len).sum::<usize>()
+            + self
+                .iterative_deepening
+                .as_ref()
+                .map_or(0, |search| search.stack.iter().map(Vec::len).sum::<usize>())
+            + self
+                .bidirectional_search
+                .as_ref()
+                .map_or(0, |search| search.heap.len())
+            + self
+                .feature_space
+                .as_ref()
+                .map_or(0, FeatureSpace::state_count)
+    }
+
+    /// Returns the number of states in the visited tables.
+    fn visited_nodes(&self) -> usize {
+        self.visited.len()
+            + self
+                .shared_visited
+                .iter()
+                .map(|visited| visited.lock().unwrap().len())
+                .sum::<usize>()
+            + self
+                .iterative_deepening
+                .as_ref()
+                .map_or(0, |search| search.table.entry_count())
+            + self
+                .bidirectional_search
+                .as_ref()
+                .map_or(0, |search| search.table.len())
+            + self
+                .anytime_search
+                .as_ref()
+                .map_or(0, |search| search.visited.len())
+    }
+
+    /// Returns the approximate memory used by the search, in bytes.
+    ///
+    /// Only the states, the visited keys and the expanded nodes are counted,
+    /// the overhead of the containers is ignored.
+    fn memory_usage(&self) -> usize {
+        let open_nodes = self.heap.len()
+            + self.open_lists.iter().map(BinaryHeap::len).sum::<usize>()
+            + self
+                .iterative_deepening
+                .as_ref()
+                .map_or(0, |search| search.stack.iter().map(Vec::len).sum::<usize>());
+        let visited_nodes = self.visited.len()
+            + self
+                .anytime_search
+                .as_ref()
+                .map_or(0, |search| search.visited.len())
+            + self
+                .shared_visited
+                .iter()
+                .map(|visited| visited.lock().unwrap().len())
+                .sum::<usize>()
+            + self
+                .iterative_deepening
+                .as_ref()
+                .map_or(0, |search| search.table.entry_count());
+        open_nodes * self.state_size()
+            + visited_nodes * self.key_size()
+            + self.arenas.iter().map(Arena::memory_usage).sum::<usize>()
+            + self
+                .iterative_deepening
+                .as_ref()
+                .map_or(0, |search| search.table.memory_usage())
+            + self
+                .bidirectional_search
+                .as_ref()
+                .map_or(0, |search| search.memory_usage(self))
+            + self
+                .feature_space
+                .as_ref()
+                .map_or(0, |space| space.memory_usage(self))
+    }
+
+    /// Returns the approximate memory used by a state waiting to be expanded, in bytes.
+    pub fn state_size(&self) -> usize {
+        size_of::<State>() + self.boxes_size()
+    }
+
+    /// Returns the approximate memory used by a visited key, in bytes.
+    pub fn key_size(&self) -> usize {
+        size_of::<StateKey>() + self.boxes_size()
+    }
+
+    /// Returns the memory used by the bitset of the boxes, in bytes.
+    fn boxes_size(&self) -> usize {
+        self.floor.count().div_ceil(64) * size_of::<u64>()
+    }
+
+    /// Checks if the memory used by the search exceeds the memory limit.
+    ///
+    /// `untracked` is the memory used by the parts of the search which are
+    /// not stored in the solver while the search is running.
+    fn is_out_of_memory(&self, untracked: usize) -> bool {
+        self.memory_limit
+            .is_some_and(|limit| self.memory_usage() + untracked > limit)
+    }
+
+    /// Keeps the memory used by the search within the limits.
+    ///
+    /// The beam search drops the worst states of the open list when it holds
+    /// more than `BEAM_WIDTH` states or the memory limit is exceeded. The
+    /// other strategies never drop states and fail instead.
+    fn limit_memory(&mut self, untracked: usize) -> Result<()> {
+        if self.strategy == Strategy::Beam && self.heap.len() > BEAM_WIDTH {
+            Self::shrink_heap(&mut self.heap);
+        }
+        if !self.is_out_of_memory(untracked) {
+            return Ok(());
+        }
+        if self.strategy == Strategy::Beam {
+            Self::shrink_heap(&mut self.heap);
+            if !self.is_out_of_memory(untracked) {
+                return Ok(());
+            }
+        }
+        Err(SolveError::OutOfMemory)
+    }
+
+    /// Returns the number of states and pushes pruned so far.
+    pub fn pruning_stats(&self) -> PruningStats {
+        self.pruning_counters.snapshot()
+    }
+
+    /// Returns the best state in the open lists, or `None` if they are empty.
+    ///
+    /// For the iterative deepening search, returns the next state of the current path.
+    pub fn best_state(&self) -> Option<&State> {
+        if let Some(search) = &self.iterative_deepening {
+            return search.stack.iter().rev().find_map(|states| states.last());
+        }
+        if let Some(space) = &self.feature_space {
+            return space.best_state();
+        }
+        self.heap
+            .peek()
+            .into_iter()
+            .chain(self.open_lists.iter().filter_map(BinaryHeap::peek))
+            .max()
+    }
+
+    /// Returns the pushes leading from the initial state to the node, in order.
+    pub fn pushes(&self, node: Node) -> Vec<Push> {
+        let mut pushes: Vec<_> = node.push.into_iter().collect();
+        let mut parent = node.parent;
+        while let Some(id) = parent {
+            let node = self.node(id);
+            pushes.extend(node.push);
+            parent = node.parent;
+        }
+        pushes.reverse();
+        pushes
+    }
+
+    /// Converts pushes from the initial state into actions.
+    ///
+    /// Returns `None` if the player can not reach the position of a push.
+    pub fn pushes_to_actions(&self, pushes: &[Push]) -> Option<Actions> {
+        let mut player_position = self.map.player_position();
+        let mut box_positions = self.map.box_positions().clone();
+        let mut actions = Actions::new();
+        for push in pushes {
+            let push_position = push.box_position - &push.direction.into();
+            let path = find_path(player_position, push_position, |position| {
+                !self.map[position].intersects(Tiles::Wall) && !box_positions.contains(&position)
+            })?;
+            actions.extend(
+                path.windows(2)
+                    .map(|pos| Direction::try_from(pos[1] - pos[0]).unwrap())
+                    .map(Action::Move),
+            );
+            for _ in 0..push.count {
+                actions.push(Action::Push(push.direction));
+            }
+            box_positions.remove(&push.box_position);
+            box_positions.insert(push.destination());
+            player_position = push.destination() - &push.direction.into();
+
+            if let Some(index) = push.goal_macro {
+                let goal_macro = self.goal_room()?.goal_macro(index as usize);
+                let path = find_path(player_position, goal_macro.player_position, |position| {
+                    !self.map[position].intersects(Tiles::Wall)
+                        && !box_positions.contains(&position)
+                })?;
+                actions.extend(
+                    path.windows(2)
+                        .map(|pos| Direction::try_from(pos[1] - pos[0]).unwrap())
+                        .map(Action::Move),
+                );
+                actions.extend(goal_macro.actions.iter().copied());
+                box_positions.remove(&push.destination());
+                box_positions.insert(goal_macro.goal);
+                player_position = goal_macro.final_player_position;
+            }
         }
-        tunnels
+        Some(actions)
+    }
+
+    /// Returns the position of the box after the push, including the goal macro.
+    pub fn push_destination(&self, push: &Push) -> Vector2<i32> {
+        match push.goal_macro {
+            Some(index) => self.goal_room().unwrap().goal_macro(index as usize).goal,
+            None => push.destination(),
+        }
+    }
+
+    /// Returns the expanded node with the ID.
+    pub fn node(&self, id: NodeId) -> &Node {
+        self.arenas[id.arena()].get(id)
+    }
+
+    /// Returns the index of the floor squares.
+    pub fn floor(&self) -> &FloorIndex {
+        &self.floor
+    }
+
+    /// Returns the Zobrist keys of the level.
+    pub fn zobrist(&self) -> &Zobrist {
+        &self.zobrist
+    }
+
+    /// Returns the tunnels of the level.
+    pub fn tunnels(&self) -> &Tunnels {
+        self.tunnels.get_or_init(|| {
+            let move_optimal = self.strategy == Strategy::OptimalMovePush || self.is_anytime();
+            Tunnels::new(&self.map, self.push_distances(), move_optimal)
+        })
+    }
+
+    /// Returns the goal room of the level, if any.
+    ///
+    /// Always `None` for optimal strategies and anytime searches, since goal
+    /// macros may discard optimal solutions.
+    pub fn goal_room(&self) -> Option<&GoalRoom> {
+        if self.strategy.is_optimal() || self.is_anytime() {
+            return None;
+        }
+        self.goal_room
+            .get_or_init(|| GoalRoom::new(&self.map))
+            .as_ref()
     }
 
     /// Returns a reference to the set of lower bounds.
@@ -180,81 +1291,51 @@ impl Solver {
             LowerBoundMethod::MinimumPush => self.minimum_push_lower_bounds(),
             LowerBoundMethod::MinimumMove => self.minimum_move_lower_bounds(),
             LowerBoundMethod::ManhattanDistance => self.manhattan_distance_lower_bounds(),
+            LowerBoundMethod::MinimumMatching => self.minimum_push_lower_bounds(),
         }
     }
 
-    /// Calculates and returns the lower bounds using the minimum push method.
-    fn minimum_push_lower_bounds(&self) -> HashMap<Vector2<i32>, usize> {
-        let mut lower_bounds = HashMap::new();
-        for goal_position in self.map.goal_positions() {
-            lower_bounds.insert(*goal_position, 0);
-            let mut player_position = None;
-            for pull_direction in Direction::iter() {
-                let next_box_position = goal_position + &pull_direction.into();
-                let next_player_position = next_box_position + &pull_direction.into();
-                if self.map.in_bounds(next_player_position)
-                    && !self.map[next_player_position].intersects(Tiles::Wall)
-                    && !self.map[next_box_position].intersects(Tiles::Wall)
-                {
-                    player_position = Some(next_player_position);
-                    break;
-                }
-            }
-            if let Some(player_position) = player_position {
-                self.minimum_push_to(
-                    *goal_position,
-                    player_position,
-                    &mut lower_bounds,
-                    &mut HashSet::new(),
-                );
-            } else {
-                continue;
-            }
-        }
-        lower_bounds
+    /// Returns the push distances of the floor squares to each goal.
+    ///
+    /// `goal_distances()[goal][index]` is the minimum number of pushes needed
+    /// to move a box from the floor square with the index to the goal,
+    /// ignoring other boxes, or `usize::MAX` if it is impossible.
+    pub fn goal_distances(&self) -> &[Vec<usize>] {
+        self.goal_distances
+            .get_or_init(|| self.calculate_goal_distances())
     }
 
-    fn minimum_push_to(
-        &self,
-        box_position: Vector2<i32>,
-        player_position: Vector2<i32>,
-        lower_bounds: &mut HashMap<Vector2<i32>, usize>,
-        visited: &mut HashSet<(Vector2<i32>, Direction)>,
-    ) {
-        let player_reachable_area = compute_reachable_area(player_position, |position| {
-            !self.map[position].intersects(Tiles::Wall) && position != box_position
-        });
-        for pull_direction in Direction::iter() {
-            let next_box_position = box_position + &pull_direction.into();
-            if self.map[next_box_position].intersects(Tiles::Wall) {
-                continue;
-            }
+    /// Derives the push distances of the floor squares to each goal from the push distance tables.
+    fn calculate_goal_distances(&self) -> Vec<Vec<usize>> {
+        (0..self.push_distances().goal_count())
+            .map(|goal| {
+                (0..self.floor.count())
+                    .map(|index| {
+                        self.push_distances()
+                            .box_distance(goal, self.floor.position(index))
+                            .unwrap_or(usize::MAX)
+                    })
+                    .collect()
+            })
+            .collect()
+    }
 
-            let next_player_position = next_box_position + &pull_direction.into();
-            if !self.map.in_bounds(next_player_position)
-                || self.map[next_player_position].intersects(Tiles::Wall)
-            {
-                continue;
-            }
-            if !player_reachable_area.contains(&next_player_position) {
-                continue;
-            }
+    /// Returns the push distance tables of the level.
+    pub fn push_distances(&self) -> &PushDistances {
+        self.push_distances
+            .get_or_init(|| PushDistances::new(&self.map))
+    }
 
-            let lower_bound = *lower_bounds.get(&next_box_position).unwrap_or(&usize::MAX);
-            let new_lower_bound = lower_bounds[&box_position] + 1;
-            if !visited.insert((next_box_position, pull_direction)) {
-                continue;
-            }
-            if new_lower_bound < lower_bound {
-                lower_bounds.insert(next_box_position, new_lower_bound);
+    /// Calculates and returns the lower bounds using the minimum push method.
+    fn minimum_push_lower_bounds(&self) -> HashMap<Vector2<i32>, usize> {
+        let mut lower_bounds = HashMap::new();
+        for index in 0..self.floor.count() {
+            let position = self.floor.position(index);
+            if let Some(lower_bound) = self.push_distances().nearest_goal_distance(position) {
+                lower_bounds.insert(position, lower_bound);
             }
-            self.minimum_push_to(
-                next_box_position,
-                next_player_position,
-                lower_bounds,
-                visited,
-            );
         }
+        lower_bounds
     }
 
     /// Calculates and returns the lower bounds using the minimum move method.
@@ -320,22 +1401,21 @@ impl Solver {
         lower_bounds
     }
 
-    /// Shrinks the heap by retaining only a subset of states based on heuristics.
-    #[expect(dead_code)]
+    /// Shrinks the heap by retaining only the states whose heuristic value
+    /// does not exceed the 80th percentile.
     fn shrink_heap(heap: &mut BinaryHeap<State>) {
-        let max_pressure = 200_000;
-        if heap.len() > max_pressure {
-            let mut heuristics: Vec<_> = heap.iter().map(|state| state.heuristic()).collect();
-            heuristics.sort_unstable();
-
-            let alpha = 0.8;
-            let heuristic_median = heuristics[(heuristics.len() as f32 * alpha) as usize];
-            heap.retain(|state| state.heuristic() <= heuristic_median);
+        if heap.is_empty() {
+            return;
         }
+        let mut heuristics: Vec<_> = heap.iter().map(|state| state.heuristic()).collect();
+        heuristics.sort_unstable();
+
+        let alpha = 0.8;
+        let heuristic_median = heuristics[(heuristics.len() as f32 * alpha) as usize];
+        heap.retain(|state| state.heuristic() <= heuristic_median);
     }
 
     /// Prints the lower bounds for each position in the level.
-    #[expect(dead_code)]
     pub fn print_lower_bounds(&self) {
         for y in 0..self.map.dimensions().y {
             for x in 0..self.map.dimensions().x {
@@ -351,6 +1431,55 @@ impl Solver {
     }
 }
 
+impl SokobanSolver for Solver {
+    fn start(&mut self) {
+        self.result = None;
+        self.cancelled = false;
+    }
+
+    fn step(&mut self, budget: Duration) -> Result<Actions> {
+        if let Some(result) = &self.result {
+            return result.clone();
+        }
+        if self.cancelled {
+            return Err(SolveError::Timeout);
+        }
+        let result = self.search(budget);
+        if result != Err(SolveError::Timeout) {
+            self.result = Some(result.clone());
+        }
+        result
+    }
+
+    fn cancel(&mut self) {
+        self.cancelled = true;
+    }
+
+    fn stats(&self) -> SolverStats {
+        Solver::stats(self)
+    }
+
+    fn result(&self) -> Option<Result<Actions>> {
+        self.result.clone()
+    }
+
+    fn best_actions(&self) -> Option<Actions> {
+        self.best_state().map(|state| state.actions(self))
+    }
+
+    fn improved_solution(&mut self) -> Option<Actions> {
+        Solver::improved_solution(self)
+    }
+
+    fn lower_bounds(&self) -> HashMap<Vector2<i32>, usize> {
+        Solver::lower_bounds(self).clone()
+    }
+
+    fn deadlock_patterns(&self) -> Option<Arc<RwLock<PatternDatabase>>> {
+        Some(Solver::deadlock_patterns(self))
+    }
+}
+
 /// Calculates the Manhattan distance between two 2D vectors.
 fn manhattan_distance(a: &Vector2<i32>, b: &Vector2<i32>) -> i32 {
     (a.x - b.x).abs() + (a.y - b.y).abs()
@@ -1,15 +1,14 @@
 use std::{
-    cell::OnceCell,
     cmp::Ordering,
     collections::HashSet,
-    hash::{DefaultHasher, Hash, Hasher},
+    sync::{Arc, OnceLock},
 };
 
-use crate::solve::solver::*;
+use crate::solve::{arena::*, corral::*, encoding::*, matching::*, solver::*, stats::*};
 
 use nalgebra::Vector2;
 use soukoban::{
-    Action, Actions, Tiles, deadlock,
+    Actions, Tiles, deadlock,
     direction::Direction,
     path_finding::{compute_area_anchor, compute_reachable_area, find_path},
 };
@@ -17,25 +16,39 @@ use soukoban::{
 #[derive(Clone, Eq)]
 pub struct State {
     pub player_position: Vector2<i32>,
-    pub box_positions: HashSet<Vector2<i32>>,
-    pub actions: Actions,
+    /// Boxes which can still be pushed, the frozen boxes are not included.
+    pub boxes: BoxSet,
+    /// Boxes frozen on goals, which are never pushed again and act as walls.
+    ///
+    /// Shared with the successors until another box is frozen.
+    frozen: Arc<FrozenBoxes>,
+    pub moves: usize,
+    pub pushes: usize,
+    /// The expanded node this state was generated from.
+    parent: Option<NodeId>,
+    /// The push which leads from the parent to this state.
+    push: Option<Push>,
+    /// Zobrist hash of the boxes which can still be pushed.
+    boxes_hash: u64,
+    /// Assignment between boxes and goals, only used by the minimum matching lower bound.
+    matching: Option<Box<Matching>>,
     heuristic: usize,
-    lower_bound: OnceCell<usize>,
+    lower_bound: OnceLock<usize>,
 }
 
 impl PartialEq for State {
     fn eq(&self, other: &Self) -> bool {
-        self.player_position == other.player_position && self.box_positions == other.box_positions
+        self.player_position == other.player_position
+            && self.boxes == other.boxes
+            && self.frozen.boxes == other.frozen.boxes
     }
 }
 
-impl Hash for State {
-    fn hash<H: Hasher>(&self, state: &mut H) {
-        self.player_position.hash(state);
-        for position in &self.box_positions {
-            position.hash(state);
-        }
-    }
+/// Boxes frozen on goals and their Zobrist hash.
+#[derive(Clone, PartialEq, Eq)]
+struct FrozenBoxes {
+    boxes: BoxSet,
+    hash: u64,
 }
 
 impl Ord for State {
@@ -51,46 +64,133 @@ impl PartialOrd for State {
 }
 
 impl State {
+    /// Creates the initial state of a search.
+    ///
+    /// `boxes` and `boxes_hash` include all boxes, the frozen boxes are
+    /// separated from them.
     pub fn new(
         player_position: Vector2<i32>,
-        box_positions: HashSet<Vector2<i32>>,
-        actions: Actions,
+        mut boxes: BoxSet,
+        mut boxes_hash: u64,
         solver: &Solver,
     ) -> Self {
-        let mut instance = Self {
+        let mut frozen = Arc::new(FrozenBoxes {
+            boxes: BoxSet::new(solver.floor().count()),
+            hash: 0,
+        });
+        for index in boxes.clone().iter() {
+            frozen = freeze_boxes(
+                &mut boxes,
+                &mut boxes_hash,
+                &frozen,
+                solver.floor().position(index),
+                solver,
+                &mut PruningStats::default(),
+            );
+        }
+        let matching = (solver.lower_bound_method() == LowerBoundMethod::MinimumMatching)
+            .then(|| Box::new(Self::frozen_matching(&boxes, &frozen.boxes, solver)));
+        Self {
             player_position,
-            box_positions,
-            actions,
+            boxes,
+            frozen,
+            moves: 0,
+            pushes: 0,
+            parent: None,
+            push: None,
+            boxes_hash,
+            matching,
             heuristic: 0,
-            lower_bound: OnceCell::new(),
-        };
-        debug_assert!(instance.actions.moves() < 10_000);
-        debug_assert!(instance.actions.pushes() < 10_000);
-        debug_assert!(instance.lower_bound(solver) < 10_000);
-        instance.heuristic = match solver.strategy() {
-            Strategy::Fast => instance.lower_bound(solver) * 10_000 + instance.actions.moves(),
-            Strategy::Mixed => instance.lower_bound(solver) + instance.actions.moves(),
+            lower_bound: OnceLock::new(),
+        }
+        .with_heuristic(solver)
+    }
+
+    /// Calculates the heuristic value of the state.
+    fn with_heuristic(mut self, solver: &Solver) -> Self {
+        debug_assert!(self.moves < 10_000);
+        debug_assert!(self.pushes < 10_000);
+        debug_assert!(self.lower_bound(solver) < 10_000);
+        self.heuristic = match solver.strategy() {
+            Strategy::Fast | Strategy::FeatureSpace | Strategy::Beam => {
+                self.lower_bound(solver) * 10_000 + self.moves
+            }
+            Strategy::Mixed => self.lower_bound(solver) + self.moves,
+            Strategy::Weighted { .. } => {
+                self.lower_bound(solver) * solver.heuristic_weight() + self.moves
+            }
             Strategy::OptimalMovePush => {
-                instance.actions.moves() * 100_000_000
-                    + instance.actions.pushes() * 10_000
-                    + instance.lower_bound(solver)
+                self.moves * 100_000_000 + self.pushes * 10_000 + self.lower_bound(solver)
             }
             Strategy::OptimalPushMove => {
-                instance.actions.pushes() * 100_000_000
-                    + instance.actions.moves() * 10_000
-                    + instance.lower_bound(solver)
+                self.pushes * 100_000_000 + self.moves * 10_000 + self.lower_bound(solver)
             }
+            Strategy::IterativeDeepening => self.pushes + self.lower_bound(solver),
         };
-        instance.box_positions.shrink_to_fit();
-        instance.actions.shrink_to_fit();
-        instance
+        self
+    }
+
+    /// Returns the node to be stored in an arena when the state is expanded.
+    pub fn node(&self) -> Node {
+        Node {
+            parent: self.parent,
+            push: self.push,
+        }
+    }
+
+    /// Reconstructs the actions leading from the initial state to this state.
+    pub fn actions(&self, solver: &Solver) -> Actions {
+        solver
+            .pushes_to_actions(&solver.pushes(self.node()))
+            .unwrap()
     }
 
     /// Returns a vector of successor states for the current state.
-    pub fn successors(&self, solver: &Solver) -> Vec<State> {
+    ///
+    /// `node` is the ID of the current state in the arena.
+    pub fn successors(&self, node: NodeId, solver: &Solver) -> Vec<State> {
+        let mut stats = PruningStats::default();
+        let successors = self.expand(node, solver, &mut stats);
+        solver.record_expansion(successors.len(), stats);
+        successors
+    }
+
+    fn expand(&self, node: NodeId, solver: &Solver, stats: &mut PruningStats) -> Vec<State> {
         let mut successors = Vec::new();
         let player_reachable_area = self.player_reachable_area(solver);
-        for box_position in &self.box_positions {
+
+        let corrals = if solver.corral_pruning() {
+            Corral::find_all(self, &player_reachable_area, solver)
+        } else {
+            Vec::new()
+        };
+        // Only corrals changed by the last push need to be searched for deadlocks,
+        // the others have already been searched when expanding the parent state.
+        if let Some(push) = self.push
+            && let Some(pattern) = corrals
+                .iter()
+                .filter(|corral| corral.contains_box(solver.push_destination(&push)))
+                .find_map(|corral| corral.deadlock_pattern(self, solver))
+        {
+            stats.corral_deadlocks += 1;
+            solver.learn_deadlock_pattern(pattern);
+            return successors;
+        }
+        // PI-corral pruning may discard move optimal solutions.
+        let pi_barrier = if solver.strategy() != Strategy::OptimalMovePush && !solver.is_anytime() {
+            corrals
+                .iter()
+                .filter_map(|corral| corral.pi_barrier(&player_reachable_area, solver))
+                .min_by_key(|barrier| barrier.len())
+        } else {
+            None
+        };
+        if pi_barrier.is_some() {
+            stats.pi_corrals += 1;
+        }
+
+        for box_index in self.boxes.iter() {
+            let box_position = solver.floor().position(box_index);
             for push_direction in Direction::iter() {
                 let mut new_box_position = box_position + &push_direction.into();
                 if self.can_block_box(new_box_position, solver) {
@@ -103,51 +203,152 @@ impl State {
                 {
                     continue;
                 }
+                if pi_barrier.is_some_and(|barrier| !barrier.contains(&box_position)) {
+                    stats.pi_corral_pushes += 1;
+                    continue;
+                }
 
-                let mut new_actions = self.actions.clone();
                 let path = find_path(self.player_position, next_player_position, |position| {
                     !self.can_block_player(position, solver)
                 })
                 .unwrap();
-                new_actions.extend(
-                    path.windows(2)
-                        .map(|pos| Direction::try_from(pos[1] - pos[0]).unwrap())
-                        .map(Action::Move),
-                );
-                new_actions.push(Action::Push(push_direction));
+                let mut push_count = 1;
 
                 // skip tunnels
-                while solver
-                    .tunnels()
-                    .contains(&((new_box_position - &push_direction.into()), push_direction))
-                {
+                while solver.tunnels().continues_push(
+                    new_box_position,
+                    push_direction,
+                    |position| position != box_position && self.has_box_at(position, solver),
+                ) {
                     if self.can_block_box(new_box_position + &push_direction.into(), solver) {
                         break;
                     }
                     new_box_position += &push_direction.into();
-                    new_actions.push(Action::Push(push_direction));
+                    push_count += 1;
                 }
+                let mut new_player_position = new_box_position - &push_direction.into();
+                let mut moves = path.len() - 1 + push_count as usize;
+                let mut pushes = push_count as usize;
 
-                let mut new_box_positions = self.box_positions.clone();
-                new_box_positions.remove(box_position);
-                new_box_positions.insert(new_box_position);
+                // push boxes entering the goal room straight to their goals
+                let mut goal_macro = None;
+                if let Some(goal_room) = solver.goal_room()
+                    && new_box_position == goal_room.entrance
+                {
+                    let has_other_box_at =
+                        |position| position != box_position && self.has_box_at(position, solver);
+                    let boxes_in_room = self
+                        .boxes
+                        .iter()
+                        .chain(self.frozen.boxes.iter())
+                        .map(|index| solver.floor().position(index))
+                        .filter(|&position| {
+                            has_other_box_at(position) && goal_room.contains(position)
+                        })
+                        .count();
+                    if let Some(index) = goal_room.next_macro(boxes_in_room, has_other_box_at) {
+                        let next_macro = goal_room.goal_macro(index);
+                        if let Some(path) = find_path(
+                            new_player_position,
+                            next_macro.player_position,
+                            |position| {
+                                !solver.map[position].intersects(Tiles::Wall)
+                                    && position != new_box_position
+                                    && !has_other_box_at(position)
+                            },
+                        ) {
+                            new_box_position = next_macro.goal;
+                            new_player_position = next_macro.final_player_position;
+                            moves += path.len() - 1 + next_macro.actions.len();
+                            pushes += next_macro.pushes;
+                            goal_macro = Some(index as u16);
+                        }
+                    }
+                }
+
+                let new_box_index = solver.floor().index(new_box_position).unwrap();
+                let mut new_boxes = self.boxes.clone();
+                new_boxes.remove(box_index);
+                new_boxes.insert(new_box_index);
+                let mut new_boxes_hash = self.boxes_hash
+                    ^ solver.zobrist().box_key(box_index)
+                    ^ solver.zobrist().box_key(new_box_index);
 
                 // skip deadlocks
                 if !solver.map[new_box_position].intersects(Tiles::Goal)
                     && deadlock::is_freeze_deadlock(
                         &solver.map,
                         new_box_position,
-                        &new_box_positions,
+                        &box_positions(&new_boxes, &self.frozen.boxes, solver),
                         &mut HashSet::new(),
                     )
                 {
+                    stats.freeze_deadlocks += 1;
                     continue;
                 }
 
-                let new_player_position = new_box_position - &push_direction.into();
+                if solver.matches_deadlock_pattern(
+                    |index| new_boxes.contains(index) || self.frozen.boxes.contains(index),
+                    new_box_index,
+                    new_player_position,
+                ) {
+                    stats.pattern_deadlocks += 1;
+                    continue;
+                }
+
+                let new_frozen = if solver.map[new_box_position].intersects(Tiles::Goal) {
+                    freeze_boxes(
+                        &mut new_boxes,
+                        &mut new_boxes_hash,
+                        &self.frozen,
+                        new_box_position,
+                        solver,
+                        stats,
+                    )
+                } else {
+                    self.frozen.clone()
+                };
 
-                let new_state =
-                    State::new(new_player_position, new_box_positions, new_actions, solver);
+                let new_matching = self.matching.as_ref().map(|matching| {
+                    Box::new(if Arc::ptr_eq(&new_frozen, &self.frozen) {
+                        matching.moved(box_index, new_box_index, solver.goal_distances())
+                    } else {
+                        Self::frozen_matching(&new_boxes, &new_frozen.boxes, solver)
+                    })
+                });
+                // skip states where the boxes can not be assigned to distinct goals
+                if new_matching.as_ref().is_some_and(|matching| {
+                    matching.total_distance(solver.goal_distances()).is_none()
+                }) {
+                    continue;
+                }
+
+                let new_state = State {
+                    player_position: new_player_position,
+                    boxes: new_boxes,
+                    frozen: new_frozen,
+                    moves: self.moves + moves,
+                    pushes: self.pushes + pushes,
+                    parent: Some(node),
+                    push: Some(Push {
+                        box_position,
+                        direction: push_direction,
+                        count: push_count,
+                        goal_macro,
+                    }),
+                    boxes_hash: new_boxes_hash,
+                    matching: new_matching,
+                    heuristic: 0,
+                    lower_bound: OnceLock::new(),
+                }
+                .with_heuristic(solver);
+                // skip states which can not lead to a better solution
+                if solver
+                    .move_bound()
+                    .is_some_and(|bound| new_state.moves + new_state.lower_bound(solver) >= bound)
+                {
+                    continue;
+                }
                 successors.push(new_state);
             }
         }
@@ -164,22 +365,34 @@ impl State {
         self.heuristic
     }
 
-    /// Returns a normalized clone of the current state.
-    pub fn normalized(&self, solver: &Solver) -> Self {
-        let mut instance = self.clone();
-        instance.player_position = self.normalized_player_position(solver);
-        instance
+    /// Returns the exact key of the normalized state.
+    ///
+    /// The frozen boxes are part of the key, since the goals left for the
+    /// other boxes depend on them.
+    pub fn normalized_key(&self, solver: &Solver) -> StateKey {
+        StateKey::new(
+            self.boxes.union(&self.frozen.boxes),
+            self.boxes_hash ^ self.frozen.hash,
+            self.normalized_player_position(solver),
+            solver.map.dimensions(),
+            solver.zobrist(),
+        )
     }
 
-    /// Returns a normalized hash of the current state.
-    pub fn normalized_hash(&self, solver: &Solver) -> u64 {
-        let mut hasher = DefaultHasher::new();
-        self.normalized(solver).hash(&mut hasher);
-        hasher.finish()
+    /// Returns the exact key of the state, with the player position instead
+    /// of the normalized one.
+    pub fn exact_key(&self, solver: &Solver) -> StateKey {
+        StateKey::new(
+            self.boxes.union(&self.frozen.boxes),
+            self.boxes_hash ^ self.frozen.hash,
+            self.player_position,
+            solver.map.dimensions(),
+            solver.zobrist(),
+        )
     }
 
     /// Returns the lower bound value for the current state.
-    fn lower_bound(&self, solver: &Solver) -> usize {
+    pub fn lower_bound(&self, solver: &Solver) -> usize {
         *self
             .lower_bound
             .get_or_init(|| self.calculate_lower_bound(solver))
@@ -187,9 +400,18 @@ impl State {
 
     /// Calculates and returns the lower bound value for the current state.
     fn calculate_lower_bound(&self, solver: &Solver) -> usize {
+        if let Some(matching) = &self.matching {
+            return matching
+                .total_distance(solver.goal_distances())
+                .unwrap_or(10_000 - 1);
+        }
+        // frozen boxes are on goals
         let mut sum: usize = 0;
-        for box_position in &self.box_positions {
-            match solver.lower_bounds().get(box_position) {
+        for box_index in self.boxes.iter() {
+            match solver
+                .lower_bounds()
+                .get(&solver.floor().position(box_index))
+            {
                 Some(lower_bound) => sum += lower_bound,
                 None => return 10_000 - 1,
             }
@@ -197,16 +419,39 @@ impl State {
         sum
     }
 
+    /// Returns the boxes frozen on goals.
+    pub fn frozen_boxes(&self) -> &BoxSet {
+        &self.frozen.boxes
+    }
+
+    /// Computes the matching between the boxes and the goals which are not
+    /// occupied by frozen boxes.
+    fn frozen_matching(boxes: &BoxSet, frozen: &BoxSet, solver: &Solver) -> Matching {
+        let distances = solver.goal_distances();
+        // only the goal of a box is at distance 0 from it
+        let goals = (0..distances.len())
+            .filter(|&goal| frozen.iter().all(|index| distances[goal][index] != 0));
+        Matching::with_goals(boxes.iter(), goals, distances)
+    }
+
     /// Checks if a position can block the player's movement.
     fn can_block_player(&self, position: Vector2<i32>, solver: &Solver) -> bool {
-        solver.map[position].intersects(Tiles::Wall) || self.box_positions.contains(&position)
+        solver.map[position].intersects(Tiles::Wall) || self.has_box_at(position, solver)
     }
 
     /// Checks if a position can block a box's movement.
     fn can_block_box(&self, position: Vector2<i32>, solver: &Solver) -> bool {
         solver.map[position].intersects(Tiles::Wall /* | Tiles::Deadlock */)
-            || !solver.lower_bounds().contains_key(&position)
-            || self.box_positions.contains(&position)
+            || solver.push_distances().is_dead_square(position)
+            || self.has_box_at(position, solver)
+    }
+
+    /// Checks if there is a box at the position.
+    pub fn has_box_at(&self, position: Vector2<i32>, solver: &Solver) -> bool {
+        solver
+            .floor()
+            .index(position)
+            .is_some_and(|index| self.boxes.contains(index) || self.frozen.boxes.contains(index))
     }
 
     /// Returns the normalized player position based on reachable area.
@@ -215,9 +460,94 @@ impl State {
     }
 
     /// Returns the reachable area for the player in the current state.
-    fn player_reachable_area(&self, solver: &Solver) -> HashSet<Vector2<i32>> {
+    pub fn player_reachable_area(&self, solver: &Solver) -> HashSet<Vector2<i32>> {
         compute_reachable_area(self.player_position, |position| {
             !self.can_block_player(position, solver)
         })
     }
 }
+
+/// Returns the positions of the boxes, including the frozen boxes.
+fn box_positions(boxes: &BoxSet, frozen: &BoxSet, solver: &Solver) -> HashSet<Vector2<i32>> {
+    boxes
+        .iter()
+        .chain(frozen.iter())
+        .map(|index| solver.floor().position(index))
+        .collect()
+}
+
+/// Freezes the box on the position if it is on a goal and can never be pushed
+/// again, then the boxes on goals next to it which are frozen by it in turn.
+///
+/// Frozen boxes are removed from `boxes` and `boxes_hash`. Returns the shared
+/// frozen boxes unchanged if no box is frozen.
+fn freeze_boxes(
+    boxes: &mut BoxSet,
+    boxes_hash: &mut u64,
+    frozen: &Arc<FrozenBoxes>,
+    position: Vector2<i32>,
+    solver: &Solver,
+    stats: &mut PruningStats,
+) -> Arc<FrozenBoxes> {
+    let mut new_frozen: Option<FrozenBoxes> = None;
+    let mut positions = vec![position];
+    while let Some(position) = positions.pop() {
+        let Some(index) = solver.floor().index(position) else {
+            continue;
+        };
+        let current = new_frozen.as_ref().unwrap_or(frozen);
+        if !boxes.contains(index)
+            || !solver.map[position].intersects(Tiles::Goal)
+            || !is_frozen(position, boxes, &current.boxes, solver, &mut HashSet::new())
+        {
+            continue;
+        }
+        let key = solver.zobrist().box_key(index);
+        let new_frozen = new_frozen.get_or_insert_with(|| (**frozen).clone());
+        new_frozen.boxes.insert(index);
+        new_frozen.hash ^= key;
+        boxes.remove(index);
+        *boxes_hash ^= key;
+        stats.frozen_boxes += 1;
+        positions.extend(Direction::iter().map(|direction| position + &direction.into()));
+    }
+    new_frozen.map_or_else(|| frozen.clone(), Arc::new)
+}
+
+/// Checks if the box on the position can never be pushed, along both axes.
+///
+/// A box can not be pushed along an axis if a wall or a frozen box is next to
+/// it, if both squares next to it are dead squares, or if a box on a goal next
+/// to it can not be pushed either. The other boxes are assumed to stay.
+fn is_frozen(
+    position: Vector2<i32>,
+    boxes: &BoxSet,
+    frozen: &BoxSet,
+    solver: &Solver,
+    visited: &mut HashSet<Vector2<i32>>,
+) -> bool {
+    if !visited.insert(position) {
+        return true;
+    }
+    let is_in = |set: &BoxSet, position: Vector2<i32>| {
+        solver
+            .floor()
+            .index(position)
+            .is_some_and(|index| set.contains(index))
+    };
+    [Direction::Left, Direction::Up]
+        .into_iter()
+        .all(|direction| {
+            let neighbors = [position + &direction.into(), position - &direction.into()];
+            neighbors.iter().any(|&neighbor| {
+                solver.map[neighbor].intersects(Tiles::Wall) || is_in(frozen, neighbor)
+            }) || neighbors
+                .iter()
+                .all(|&neighbor| solver.push_distances().is_dead_square(neighbor))
+                || neighbors.iter().any(|&neighbor| {
+                    is_in(boxes, neighbor)
+                        && solver.map[neighbor].intersects(Tiles::Goal)
+                        && is_frozen(neighbor, boxes, frozen, solver, visited)
+                })
+        })
+}
@@ -0,0 +1,173 @@
+use std::{
+    fmt,
+    ops::AddAssign,
+    sync::atomic::{AtomicUsize, Ordering},
+    time::Duration,
+};
+
+/// Number of states or pushes pruned by each technique.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct PruningStats {
+    /// Pushes into freeze deadlocks.
+    pub freeze_deadlocks: usize,
+    /// States proven unsolvable by a corral deadlock search.
+    pub corral_deadlocks: usize,
+    /// Pushes into learned deadlock patterns.
+    pub pattern_deadlocks: usize,
+    /// States whose successors were restricted to the pushes of a PI-corral.
+    pub pi_corrals: usize,
+    /// Pushes skipped because of a PI-corral.
+    pub pi_corral_pushes: usize,
+    /// Boxes frozen on goals, which are no longer pushed.
+    pub frozen_boxes: usize,
+}
+
+impl AddAssign for PruningStats {
+    fn add_assign(&mut self, other: Self) {
+        self.freeze_deadlocks += other.freeze_deadlocks;
+        self.corral_deadlocks += other.corral_deadlocks;
+        self.pattern_deadlocks += other.pattern_deadlocks;
+        self.pi_corrals += other.pi_corrals;
+        self.pi_corral_pushes += other.pi_corral_pushes;
+        self.frozen_boxes += other.frozen_boxes;
+    }
+}
+
+impl fmt::Display for PruningStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "freeze deadlocks: {}, corral deadlocks: {}, pattern deadlocks: {}, PI-corrals: {} ({} pushes skipped), frozen boxes: {}",
+            self.freeze_deadlocks,
+            self.corral_deadlocks,
+            self.pattern_deadlocks,
+            self.pi_corrals,
+            self.pi_corral_pushes,
+            self.frozen_boxes
+        )
+    }
+}
+
+/// Pruning counters shared by the search threads.
+#[derive(Default)]
+pub struct PruningCounters {
+    freeze_deadlocks: AtomicUsize,
+    corral_deadlocks: AtomicUsize,
+    pattern_deadlocks: AtomicUsize,
+    pi_corrals: AtomicUsize,
+    pi_corral_pushes: AtomicUsize,
+    frozen_boxes: AtomicUsize,
+}
+
+impl PruningCounters {
+    /// Adds the stats collected while expanding a state.
+    pub fn add(&self, stats: PruningStats) {
+        self.freeze_deadlocks
+            .fetch_add(stats.freeze_deadlocks, Ordering::Relaxed);
+        self.corral_deadlocks
+            .fetch_add(stats.corral_deadlocks, Ordering::Relaxed);
+        self.pattern_deadlocks
+            .fetch_add(stats.pattern_deadlocks, Ordering::Relaxed);
+        self.pi_corrals
+            .fetch_add(stats.pi_corrals, Ordering::Relaxed);
+        self.pi_corral_pushes
+            .fetch_add(stats.pi_corral_pushes, Ordering::Relaxed);
+        self.frozen_boxes
+            .fetch_add(stats.frozen_boxes, Ordering::Relaxed);
+    }
+
+    /// Returns the current values of the counters.
+    pub fn snapshot(&self) -> PruningStats {
+        PruningStats {
+            freeze_deadlocks: self.freeze_deadlocks.load(Ordering::Relaxed),
+            corral_deadlocks: self.corral_deadlocks.load(Ordering::Relaxed),
+            pattern_deadlocks: self.pattern_deadlocks.load(Ordering::Relaxed),
+            pi_corrals: self.pi_corrals.load(Ordering::Relaxed),
+            pi_corral_pushes: self.pi_corral_pushes.load(Ordering::Relaxed),
+            frozen_boxes: self.frozen_boxes.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// Progress of a search.
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub struct SolverStats {
+    /// Nodes whose successors have been generated.
+    pub expanded_nodes: usize,
+    /// Successors generated by the expansions.
+    pub generated_nodes: usize,
+    /// Generated nodes which had already been visited.
+    pub duplicates: usize,
+    pub pruning: PruningStats,
+    /// Nodes waiting to be expanded.
+    pub open_nodes: usize,
+    /// Nodes in the visited tables.
+    pub visited_nodes: usize,
+    /// Approximate memory used by the search, in bytes.
+    pub memory_usage: usize,
+    /// Lower bound of the best open node.
+    pub best_lower_bound: Option<usize>,
+    /// Time spent searching.
+    pub elapsed: Duration,
+}
+
+impl SolverStats {
+    /// Returns the number of nodes expanded per second.
+    pub fn nodes_per_second(&self) -> f64 {
+        if self.elapsed.is_zero() {
+            return 0.0;
+        }
+        self.expanded_nodes as f64 / self.elapsed.as_secs_f64()
+    }
+}
+
+impl fmt::Display for SolverStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "expanded: {}, generated: {}, duplicates: {}, open: {}, visited: {}, memory: {:.1} MiB, best lower bound: ",
+            self.expanded_nodes,
+            self.generated_nodes,
+            self.duplicates,
+            self.open_nodes,
+            self.visited_nodes,
+            self.memory_usage as f64 / (1024.0 * 1024.0),
+        )?;
+        match self.best_lower_bound {
+            Some(lower_bound) => write!(f, "{lower_bound}")?,
+            None => write!(f, "-")?,
+        }
+        write!(f, ", {:.0} nodes/s", self.nodes_per_second())
+    }
+}
+
+/// Node counters shared by the search threads.
+#[derive(Default)]
+pub struct SearchCounters {
+    expanded_nodes: AtomicUsize,
+    generated_nodes: AtomicUsize,
+    duplicates: AtomicUsize,
+}
+
+impl SearchCounters {
+    /// Counts an expanded node and its successors.
+    pub fn add_expansion(&self, generated_nodes: usize) {
+        self.expanded_nodes.fetch_add(1, Ordering::Relaxed);
+        self.generated_nodes
+            .fetch_add(generated_nodes, Ordering::Relaxed);
+    }
+
+    /// Counts a generated node which had already been visited.
+    pub fn add_duplicate(&self) {
+        self.duplicates.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Returns the expanded nodes, generated nodes and duplicates.
+    pub fn snapshot(&self) -> (usize, usize, usize) {
+        (
+            self.expanded_nodes.load(Ordering::Relaxed),
+            self.generated_nodes.load(Ordering::Relaxed),
+            self.duplicates.load(Ordering::Relaxed),
+        )
+    }
+}
@@ -0,0 +1,64 @@
+use crate::solve::encoding::StateKey;
+
+/// A state searched by an iteration of an iterative deepening search.
+struct Entry {
+    key: StateKey,
+    threshold: usize,
+    pushes: usize,
+}
+
+/// A fixed size table of states searched by an iterative deepening search.
+///
+/// Each state is stored in the slot selected by its hash and replaces the
+/// previous state of the slot, so the memory used never grows. A replaced
+/// state may be searched again, which only costs time.
+pub struct TranspositionTable {
+    entries: Vec<Option<Entry>>,
+    /// Number of occupied slots.
+    entry_count: usize,
+}
+
+impl TranspositionTable {
+    /// Creates a new table with the number of slots.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            entries: (0..capacity.max(1)).map(|_| None).collect(),
+            entry_count: 0,
+        }
+    }
+
+    /// Records a state reached with the number of pushes in the iteration with
+    /// the threshold.
+    ///
+    /// Returns `false` if the state has already been searched in the same
+    /// iteration with at most as many pushes.
+    pub fn insert(&mut self, key: StateKey, threshold: usize, pushes: usize) -> bool {
+        let slot = (key.zobrist_hash() % self.entries.len() as u64) as usize;
+        if let Some(entry) = &self.entries[slot]
+            && entry.key == key
+            && entry.threshold == threshold
+            && entry.pushes <= pushes
+        {
+            return false;
+        }
+        if self.entries[slot].is_none() {
+            self.entry_count += 1;
+        }
+        self.entries[slot] = Some(Entry {
+            key,
+            threshold,
+            pushes,
+        });
+        true
+    }
+
+    /// Returns the number of states in the table.
+    pub fn entry_count(&self) -> usize {
+        self.entry_count
+    }
+
+    /// Returns the memory used by the slots of the table, in bytes.
+    pub fn memory_usage(&self) -> usize {
+        self.entries.len() * size_of::<Option<Entry>>()
+    }
+}
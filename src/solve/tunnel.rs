@@ -0,0 +1,180 @@
+use std::collections::{HashMap, HashSet};
+
+use nalgebra::Vector2;
+use soukoban::{Map, Tiles, direction::Direction, path_finding::compute_reachable_area};
+
+use crate::solve::push_distance::PushDistances;
+
+/// A dead-end corridor which can only be entered through a single square.
+///
+/// The player can never get behind a box on the entrance, so the box can only
+/// be pushed further into the corridor.
+#[derive(Clone, PartialEq, Eq, Debug)]
+struct OneWayCorridor {
+    /// Squares of the corridor, excluding the entrance.
+    squares: Vec<Vector2<i32>>,
+    goal_count: usize,
+}
+
+/// Tunnels of a level, where pushing a box further is never worse than
+/// stopping, even for move optimal solutions.
+pub struct Tunnels {
+    /// Squares and directions in which a box pushed onto the square is pushed
+    /// further, with the one-way corridor it is pushed into, if any.
+    pushes: HashMap<(Vector2<i32>, Direction), Option<OneWayCorridor>>,
+    /// Corridor squares which only the player can pass, since they are dead
+    /// squares for boxes.
+    player_tunnels: HashSet<Vector2<i32>>,
+}
+
+impl Tunnels {
+    /// Analyzes the tunnels of the map.
+    ///
+    /// If `move_optimal` is `true`, the sides of straight tunnels must be
+    /// walls, since the player may leave through a player tunnel and save
+    /// moves by pushing the box further later.
+    pub fn new(map: &Map, push_distances: &PushDistances, move_optimal: bool) -> Self {
+        let is_wall = |position: Vector2<i32>| map[position].intersects(Tiles::Wall);
+        let floor = compute_reachable_area(map.player_position(), |position| !is_wall(position));
+
+        let player_tunnels: HashSet<_> = floor
+            .iter()
+            .copied()
+            .filter(|&position| {
+                push_distances.is_dead_square(position) && is_corridor(map, position)
+            })
+            .collect();
+
+        let mut pushes = HashMap::new();
+        for &box_position in &floor {
+            for direction in Direction::iter() {
+                let player_position = box_position - &direction.into();
+                let next_box_position = box_position + &direction.into();
+                if is_wall(player_position)
+                    || push_distances.is_dead_square(next_box_position)
+                    || !is_axis_restricted(map, push_distances, box_position, direction)
+                {
+                    continue;
+                }
+
+                // The player is confined in a straight tunnel behind the box.
+                //  .      .      .
+                // #$# or #$_ or _$#
+                // #@#    #@#    #@#
+                let is_straight_tunnel = perpendicular(direction).into_iter().all(|side| {
+                    let position = player_position + &side.into();
+                    is_wall(position) || (!move_optimal && player_tunnels.contains(&position))
+                });
+                let is_goal = map[box_position].intersects(Tiles::Goal);
+
+                let corridor = one_way_corridor(map, box_position, direction);
+                let is_one_way = corridor
+                    .as_ref()
+                    .is_some_and(|corridor| corridor.goal_count > 0);
+                if !is_goal && (is_straight_tunnel || is_one_way) {
+                    pushes.insert((box_position, direction), None);
+                } else if is_one_way {
+                    // A box on a goal is only pushed further if the goals of
+                    // the corridor still need boxes.
+                    pushes.insert((box_position, direction), corridor);
+                }
+            }
+        }
+
+        Self {
+            pushes,
+            player_tunnels,
+        }
+    }
+
+    /// Checks if a box pushed onto the position in the direction is pushed further.
+    pub fn continues_push(
+        &self,
+        box_position: Vector2<i32>,
+        direction: Direction,
+        has_box_at: impl Fn(Vector2<i32>) -> bool,
+    ) -> bool {
+        match self.pushes.get(&(box_position, direction)) {
+            None => false,
+            Some(None) => true,
+            Some(Some(corridor)) => {
+                corridor
+                    .squares
+                    .iter()
+                    .filter(|&&position| has_box_at(position))
+                    .count()
+                    < corridor.goal_count
+            }
+        }
+    }
+
+    /// Checks if the position is in a corridor which only the player can pass.
+    pub fn is_player_tunnel(&self, position: Vector2<i32>) -> bool {
+        self.player_tunnels.contains(&position)
+    }
+}
+
+/// Returns the directions perpendicular to the direction.
+fn perpendicular(direction: Direction) -> [Direction; 2] {
+    match direction {
+        Direction::Up | Direction::Down => [Direction::Left, Direction::Right],
+        Direction::Left | Direction::Right => [Direction::Up, Direction::Down],
+    }
+}
+
+/// Checks if the position is in a corridor one square wide, which may turn.
+fn is_corridor(map: &Map, position: Vector2<i32>) -> bool {
+    let is_wall = |position: Vector2<i32>| map[position].intersects(Tiles::Wall);
+    let exits: Vec<Vector2<i32>> = Direction::iter()
+        .map(Into::into)
+        .filter(|&offset| !is_wall(position + offset))
+        .collect();
+    match exits[..] {
+        [] | [_] => true,
+        // A turn, unlike the corner of a room, has a wall on the inside.
+        [a, b] => a + b == Vector2::zeros() || is_wall(position + a + b),
+        _ => false,
+    }
+}
+
+/// Checks if a box on the position can not be pushed perpendicular to the
+/// direction, either because the square next to it is blocked for boxes or
+/// because the player can not stand on the opposite side.
+fn is_axis_restricted(
+    map: &Map,
+    push_distances: &PushDistances,
+    box_position: Vector2<i32>,
+    direction: Direction,
+) -> bool {
+    perpendicular(direction).into_iter().all(|side| {
+        push_distances.is_dead_square(box_position + &side.into())
+            || map[box_position - &side.into()].intersects(Tiles::Wall)
+    })
+}
+
+/// Returns the corridor behind the position in the direction, if it can only
+/// be entered through the position.
+///
+/// Corridors are small, so the boxes in them can be counted on each push.
+fn one_way_corridor(
+    map: &Map,
+    entrance: Vector2<i32>,
+    direction: Direction,
+) -> Option<OneWayCorridor> {
+    let start = entrance + &direction.into();
+    let squares = compute_reachable_area(start, |position| {
+        position != entrance && !map[position].intersects(Tiles::Wall)
+    });
+    if squares.contains(&(entrance - &direction.into()))
+        || squares.iter().any(|&position| !is_corridor(map, position))
+    {
+        return None;
+    }
+    Some(OneWayCorridor {
+        goal_count: squares
+            .iter()
+            .filter(|&&position| map[position].intersects(Tiles::Goal))
+            .count(),
+        squares: squares.into_iter().collect(),
+    })
+}
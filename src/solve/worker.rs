@@ -0,0 +1,148 @@
+use std::{
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, Receiver},
+    },
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+use soukoban::Actions;
+
+use crate::solve::{
+    optimizer::{Metric, Optimizer},
+    sokoban_solver::SokobanSolver,
+    solver::SolveError,
+    stats::SolverStats,
+};
+
+/// Duration of a single search slice between two progress reports.
+const SLICE_DURATION: Duration = Duration::from_millis(50);
+
+/// Messages sent from the worker thread to its owner.
+pub enum SolverMessage {
+    /// Progress of the search.
+    Progress {
+        /// Actions leading to the best state found so far, if the solver reports it.
+        best_actions: Option<Actions>,
+        stats: SolverStats,
+    },
+    /// A better solution has been found, the search continues.
+    Improved {
+        solution: Actions,
+        stats: SolverStats,
+    },
+    /// The search has terminated.
+    Finished {
+        result: Result<Actions, SolveError>,
+        stats: SolverStats,
+    },
+}
+
+/// Runs a solver on a dedicated thread.
+///
+/// Dropping the worker cancels the search.
+pub struct SolverWorker {
+    cancelled: Arc<AtomicBool>,
+    receiver: Mutex<Receiver<SolverMessage>>,
+}
+
+impl SolverWorker {
+    /// Spawns a new thread that searches for a solution with the given solver.
+    pub fn spawn(mut solver: Box<dyn SokobanSolver>) -> Self {
+        let cancelled = Arc::new(AtomicBool::new(false));
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn({
+            let cancelled = cancelled.clone();
+            move || {
+                solver.start();
+                while !cancelled.load(Ordering::Relaxed) {
+                    let result = solver.step(SLICE_DURATION);
+                    if let Some(solution) = solver.improved_solution()
+                        && sender
+                            .send(SolverMessage::Improved {
+                                solution,
+                                stats: solver.stats(),
+                            })
+                            .is_err()
+                    {
+                        break;
+                    }
+                    match result {
+                        Err(SolveError::Timeout) if solver.result().is_none() => {
+                            if sender
+                                .send(SolverMessage::Progress {
+                                    best_actions: solver.best_actions(),
+                                    stats: solver.stats(),
+                                })
+                                .is_err()
+                            {
+                                break;
+                            }
+                        }
+                        result => {
+                            let _ = sender.send(SolverMessage::Finished {
+                                result,
+                                stats: solver.stats(),
+                            });
+                            return;
+                        }
+                    }
+                }
+                solver.cancel();
+            }
+        });
+        Self {
+            cancelled,
+            receiver: Mutex::new(receiver),
+        }
+    }
+
+    /// Requests the worker thread to stop searching.
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    /// Returns the messages received since the last call.
+    pub fn messages(&self) -> Vec<SolverMessage> {
+        self.receiver.lock().unwrap().try_iter().collect()
+    }
+}
+
+impl Drop for SolverWorker {
+    fn drop(&mut self) {
+        self.cancel();
+    }
+}
+
+/// Runs an optimizer on a dedicated thread.
+pub struct OptimizerWorker {
+    metric: Metric,
+    handle: JoinHandle<Option<Actions>>,
+}
+
+impl OptimizerWorker {
+    /// Spawns a new thread that optimizes the solution with the given optimizer.
+    pub fn spawn(optimizer: Optimizer, solution: Actions, timeout: Duration) -> Self {
+        Self {
+            metric: optimizer.metric(),
+            handle: thread::spawn(move || optimizer.optimize(&solution, timeout)),
+        }
+    }
+
+    /// Returns the metric minimized by the optimizer.
+    pub fn metric(&self) -> Metric {
+        self.metric
+    }
+
+    /// Checks if the optimizer has finished.
+    pub fn is_finished(&self) -> bool {
+        self.handle.is_finished()
+    }
+
+    /// Waits for the optimizer to finish and returns the better solution, if any.
+    pub fn join(self) -> Option<Actions> {
+        self.handle.join().unwrap()
+    }
+}
@@ -3,9 +3,18 @@ use bevy::{color::palettes::css::*, prelude::*};
 use nalgebra::Vector2;
 use soukoban::{Level, Tiles};
 
-use crate::{board, calculate_camera_default_scale, components::*, database, resources::*};
+use crate::{
+    board, calculate_camera_default_scale,
+    components::*,
+    database,
+    resources::*,
+    solve::{optimizer::Optimizer, worker::OptimizerWorker},
+};
 
-use std::{collections::HashMap, fs, path::Path, sync::Mutex};
+use std::{collections::HashMap, fs, mem, path::Path, sync::Mutex, time::Duration};
+
+/// Time limit of the solution optimizer.
+const OPTIMIZATION_TIME_LIMIT: Duration = Duration::from_secs(10);
 
 /// Sets up the database, initializes it, and loads levels from files into the database.
 pub fn setup_database(mut commands: Commands) {
@@ -54,8 +63,10 @@ pub fn spawn_board(
     config: Res<Config>,
     asset_server: Res<AssetServer>,
     mut spritesheet_layouts: ResMut<Assets<TextureAtlasLayout>>,
+    mut deadlocks: ResMut<Deadlocks>,
 ) {
     player_movement.directions.clear();
+    *deadlocks = Deadlocks::default();
 
     let database = database.lock().unwrap();
     let level = database.get_level_by_id(level_id.0).unwrap();
@@ -142,17 +153,17 @@ pub fn spawn_board(
         });
 }
 
-pub fn auto_switch_to_next_unsolved_level(
-    mut board: Query<&mut Board>,
-    mut level_id: ResMut<LevelId>,
+/// Saves the solution of the solved level, and optimizes it in the background.
+pub fn save_solution(
+    board: Query<&Board>,
+    level_id: Res<LevelId>,
+    mut solution_optimizer: ResMut<SolutionOptimizer>,
+    hint_state: Res<HintState>,
     database: Res<Database>,
     config: Res<Config>,
 ) {
-    if !config.auto_switch_to_next_unsolved_level {
-        return;
-    }
     let database = database.lock().unwrap();
-    let board = &mut board.single_mut().unwrap().board;
+    let board = &board.single().unwrap().board;
     debug_assert!(board.is_solved());
     info!("{}", "=".repeat(15));
     info!("#{} Solved!", level_id.0);
@@ -160,9 +171,61 @@ pub fn auto_switch_to_next_unsolved_level(
     info!("Pushes  : {}", board.actions().pushes());
     info!("Solution: {}", board.actions().to_string());
     database.update_solution(level_id.0, board.actions());
+    if hint_state.used {
+        info!("Hints   : used");
+        database.flag_hinted_solution(level_id.0, board.actions());
+    }
+    if config.solver.optimize_solutions {
+        let level = database.get_level_by_id(level_id.0).unwrap();
+        let optimizer = Optimizer::new(level.map().clone(), config.solver.optimization_metric);
+        solution_optimizer.workers.push((
+            level_id.0,
+            hint_state.used,
+            OptimizerWorker::spawn(optimizer, board.actions().clone(), OPTIMIZATION_TIME_LIMIT),
+        ));
+    }
+}
+
+pub fn auto_switch_to_next_unsolved_level(
+    mut level_id: ResMut<LevelId>,
+    database: Res<Database>,
+    config: Res<Config>,
+) {
+    if !config.auto_switch_to_next_unsolved_level {
+        return;
+    }
+    let database = database.lock().unwrap();
     switch_to_next_unsolved_level(&mut level_id, &database);
 }
 
+/// Saves the solutions improved by the solution optimizer.
+pub fn update_optimized_solutions(
+    mut solution_optimizer: ResMut<SolutionOptimizer>,
+    database: Res<Database>,
+) {
+    let (finished, running): (Vec<_>, Vec<_>) = mem::take(&mut solution_optimizer.workers)
+        .into_iter()
+        .partition(|(_, _, worker)| worker.is_finished());
+    solution_optimizer.workers = running;
+
+    for (level_id, hinted, worker) in finished {
+        let metric = worker.metric();
+        let Some(solution) = worker.join() else {
+            continue;
+        };
+        info!("#{} Optimized!", level_id);
+        info!("Moves   : {}", solution.moves());
+        info!("Pushes  : {}", solution.pushes());
+        info!("Solution: {}", solution.to_string());
+        let database = database.lock().unwrap();
+        database.update_solution(level_id, &solution);
+        database.update_metric_solution(level_id, metric, &solution);
+        if hinted {
+            database.flag_hinted_solution(level_id, &solution);
+        }
+    }
+}
+
 /// Imports levels from the system clipboard.
 pub fn import_from_clipboard(level_id: &mut LevelId, database: &database::Database) {
     let mut clipboard = Clipboard::new().unwrap();
@@ -1,9 +1,13 @@
 use benimator::{Animation, FrameRate};
-use bevy::{ecs::system::NonSendMarker, prelude::*, window::WindowResized, winit::WINIT_WINDOWS};
+use bevy::{
+    color::palettes::css::TOMATO, ecs::system::NonSendMarker, prelude::*, window::WindowResized,
+    winit::WINIT_WINDOWS,
+};
 use nalgebra::Vector2;
 use soukoban::{Map, direction::Direction};
 
 use crate::{
+    board,
     components::{AnimationState, Board, Box, GridPosition, MainCamera, Player},
     events::*,
     resources::*,
@@ -90,12 +94,23 @@ pub fn handle_player_movement(
     mut box_enter_goal_events: MessageWriter<BoxEnterGoal>,
     mut box_leave_goal_events: MessageWriter<BoxLeaveGoal>,
     mut level_solved_events: MessageWriter<LevelSolved>,
+    mut deadlocks: ResMut<Deadlocks>,
 ) {
     if player_movement.directions.is_empty() {
         return;
     }
 
     let board = &mut board.single_mut().unwrap().board;
+    let prevents_deadlock_push =
+        |deadlocks: &mut ResMut<Deadlocks>, board: &board::Board, direction| {
+            config.prevent_deadlock_pushes
+                && Deadlocks::is_deadlock_push(
+                    deadlocks,
+                    board,
+                    direction,
+                    config.detect_corral_deadlocks,
+                )
+        };
 
     let player_grid_position = &mut **player.single_mut().unwrap();
     if !config.instant_move {
@@ -104,6 +119,10 @@ pub fn handle_player_movement(
             return;
         }
         if let Some(direction) = player_movement.directions.pop_back() {
+            if prevents_deadlock_push(&mut deadlocks, board, direction) {
+                player_movement.directions.clear();
+                return;
+            }
             let occupied_goals_count = board
                 .map
                 .goal_positions()
@@ -136,6 +155,10 @@ pub fn handle_player_movement(
         }
     } else {
         while let Some(direction) = player_movement.directions.pop_back() {
+            if prevents_deadlock_push(&mut deadlocks, board, direction) {
+                player_movement.directions.clear();
+                break;
+            }
             board.do_action(direction);
 
             *player_grid_position += Into::<Vector2<i32>>::into(direction);
@@ -153,11 +176,26 @@ pub fn handle_player_movement(
         }
     }
 
+    Deadlocks::update(&mut deadlocks, board, config.detect_corral_deadlocks);
     if board.is_solved() {
         level_solved_events.write_default();
     }
 }
 
+/// Tints the boxes which are part of a deadlock.
+pub fn tint_deadlocked_boxes(
+    deadlocks: Res<Deadlocks>,
+    mut boxes: Query<(&GridPosition, &mut Sprite), With<Box>>,
+) {
+    for (grid_position, mut sprite) in &mut boxes {
+        sprite.color = if deadlocks.boxes.contains(&grid_position.0) {
+            TOMATO.into()
+        } else {
+            Color::WHITE
+        };
+    }
+}
+
 /// Applies smooth motion to tiles based on their grid positions.
 pub fn smooth_tile_motion(
     mut tiles: Query<(&mut Transform, &GridPosition)>,
@@ -215,10 +253,14 @@ pub fn update_grid_position_from_board(
     mut player: Query<&mut GridPosition, With<Player>>,
     mut boxes: Query<&mut GridPosition, (With<Box>, Without<Player>)>,
     board: Query<&Board>,
+    mut deadlocks: ResMut<Deadlocks>,
+    config: Res<Config>,
 ) {
     update_grid_position_events.clear();
 
-    let map = &board.single().unwrap().board.map;
+    let board = &board.single().unwrap().board;
+    Deadlocks::update(&mut deadlocks, board, config.detect_corral_deadlocks);
+    let map = &board.map;
 
     let player_grid_position = &mut player.single_mut().unwrap().0;
     player_grid_position.x = map.player_position().x;
@@ -3,12 +3,34 @@
 #[cfg(test)]
 mod tests {
     // use super::test::Bencher;
-    use crate::{board::Board, solve::solver::*};
+    use crate::{
+        board::Board,
+        solve::{matching::Matching, solver::*},
+    };
     use soukoban::Level;
     use std::{ops::RangeBounds, time::Duration};
 
+    #[cfg(all(unix, not(debug_assertions)))]
+    use crate::solve::{external::ExternalSolver, sokoban_solver::SokobanSolver};
     #[cfg(not(debug_assertions))]
-    use std::fs;
+    use crate::{
+        database::Database,
+        solve::{
+            benchmark::*, config::*, decomposition::*, encoding::BoxSet, optimizer::*,
+            state::State, worker::*,
+        },
+    };
+    #[cfg(not(debug_assertions))]
+    use nalgebra::Vector2;
+    #[cfg(not(debug_assertions))]
+    use soukoban::{Actions, Map, direction::Direction};
+    #[cfg(not(debug_assertions))]
+    use std::{
+        collections::{HashSet, VecDeque},
+        fs,
+        path::Path,
+        str::FromStr,
+    };
 
     #[allow(dead_code)]
     fn solve<R: RangeBounds<usize> + IntoIterator<Item = usize>>(
@@ -47,6 +69,92 @@ mod tests {
         failed
     }
 
+    /// Loads the levels of a collection.
+    #[cfg(not(debug_assertions))]
+    fn load_levels(path: &str) -> Vec<Level> {
+        Level::load_from_str(&fs::read_to_string(path).unwrap())
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap()
+    }
+
+    /// Asserts that the actions solve the level.
+    #[cfg(not(debug_assertions))]
+    fn assert_solution(map: &Map, actions: &Actions) {
+        let mut board = Board::with_map(map.clone());
+        for action in &**actions {
+            board.do_action(action.direction());
+        }
+        assert!(board.is_solved());
+    }
+
+    /// Creates an in-memory database containing the levels.
+    #[cfg(not(debug_assertions))]
+    fn database_with_levels(levels: &[Level]) -> Database {
+        let database = Database::from_memory();
+        database.initialize();
+        database.import_levels(levels);
+        database
+    }
+
+    #[cfg(not(debug_assertions))]
+    fn push_optimal_solver(map: &Map) -> Solver {
+        Solver::new(
+            map.clone(),
+            Strategy::OptimalPushMove,
+            LowerBoundMethod::MinimumPush,
+        )
+    }
+
+    /// Asserts that both solvers find solutions with the same number of pushes.
+    #[cfg(not(debug_assertions))]
+    fn assert_same_pushes(
+        levels: &[Level],
+        expected_solver: impl Fn(&Map) -> Solver,
+        solver: impl Fn(&Map) -> Solver,
+    ) {
+        for level in levels {
+            let expected = expected_solver(level.map())
+                .search(Duration::from_secs(10))
+                .unwrap();
+            let solution = solver(level.map()).search(Duration::from_secs(10)).unwrap();
+            assert_solution(level.map(), &solution);
+            assert_eq!(solution.pushes(), expected.pushes());
+        }
+    }
+
+    /// Returns the minimum number of moves of a small level, by a breadth first
+    /// search over every move.
+    #[cfg(not(debug_assertions))]
+    fn minimum_moves(map: &Map) -> Option<usize> {
+        let key = |map: &Map| {
+            let mut box_positions: Vec<_> = map
+                .box_positions()
+                .iter()
+                .map(|position| (position.x, position.y))
+                .collect();
+            box_positions.sort();
+            (map.player_position(), box_positions)
+        };
+        let mut visited = HashSet::from([key(map)]);
+        let mut queue = VecDeque::from([(Board::with_map(map.clone()), 0)]);
+        while let Some((board, moves)) = queue.pop_front() {
+            if board.is_solved() {
+                return Some(moves);
+            }
+            for direction in Direction::iter() {
+                if !board.moveable(direction) {
+                    continue;
+                }
+                let mut next_board = board.clone();
+                next_board.do_action(direction);
+                if visited.insert(key(&next_board.map)) {
+                    queue.push_back((next_board, moves + 1));
+                }
+            }
+        }
+        None
+    }
+
     #[test]
     #[cfg(not(debug_assertions))]
     fn solve_microban_2() {
@@ -105,6 +213,992 @@ mod tests {
         );
     }
 
+    #[test]
+    #[cfg(not(debug_assertions))]
+    fn parallel_search_keeps_optimality() {
+        let levels = load_levels("assets/levels/microban_155.xsb");
+        assert_same_pushes(&levels[..30], push_optimal_solver, |map| {
+            let mut solver = push_optimal_solver(map);
+            solver.set_threads(4);
+            solver
+        });
+    }
+
+    #[test]
+    #[cfg(not(debug_assertions))]
+    fn minimum_matching_keeps_optimality() {
+        let levels = load_levels("assets/levels/microban_155.xsb");
+        assert_same_pushes(&levels[..30], push_optimal_solver, |map| {
+            Solver::new(
+                map.clone(),
+                Strategy::OptimalPushMove,
+                LowerBoundMethod::MinimumMatching,
+            )
+        });
+    }
+
+    #[test]
+    fn matching_without_enough_goals_is_infeasible() {
+        // two boxes, one goal
+        let distances = [vec![1, 0, 1]];
+        let matching = Matching::new([0, 2], &distances);
+        assert_eq!(matching.total_distance(&distances), None);
+
+        let matching = Matching::new([0], &distances);
+        assert_eq!(matching.total_distance(&distances), Some(1));
+    }
+
+    #[test]
+    #[cfg(not(debug_assertions))]
+    fn bidirectional_search_solves_levels() {
+        let levels = load_levels("assets/levels/microban_155.xsb");
+        for level in &levels[..50] {
+            let mut solver = Solver::new(
+                level.map().clone(),
+                Strategy::Fast,
+                LowerBoundMethod::MinimumMove,
+            );
+            solver.set_bidirectional(true);
+            let solution = solver.search(Duration::from_secs(10)).unwrap();
+
+            assert_solution(level.map(), &solution);
+        }
+    }
+
+    #[test]
+    #[cfg(not(debug_assertions))]
+    fn feature_space_search_solves_hard_levels() {
+        let levels = load_levels("assets/levels/box_world_100.xsb");
+        // Levels excluded from `solve_box_world`.
+        for id in [20, 81, 93] {
+            let mut solver = Solver::new(
+                levels[id].map().clone(),
+                Strategy::FeatureSpace,
+                LowerBoundMethod::MinimumMove,
+            );
+            let solution = solver.search(Duration::from_secs(10 * 4)).unwrap();
+
+            assert_solution(levels[id].map(), &solution);
+        }
+    }
+
+    #[test]
+    #[cfg(not(debug_assertions))]
+    fn iterative_deepening_keeps_optimality() {
+        let levels = load_levels("assets/levels/microban_155.xsb");
+        assert_same_pushes(&levels[..30], push_optimal_solver, |map| {
+            Solver::new(
+                map.clone(),
+                Strategy::IterativeDeepening,
+                LowerBoundMethod::MinimumMatching,
+            )
+        });
+    }
+
+    #[test]
+    #[cfg(not(debug_assertions))]
+    fn corral_pruning_keeps_optimality() {
+        let levels = load_levels("assets/levels/microban_155.xsb");
+        assert_same_pushes(
+            &levels[..50],
+            |map| {
+                let mut solver = push_optimal_solver(map);
+                solver.set_corral_pruning(false);
+                solver
+            },
+            push_optimal_solver,
+        );
+    }
+
+    #[test]
+    #[cfg(not(debug_assertions))]
+    fn optimizer_improves_solutions() {
+        let levels = load_levels("assets/levels/microban_155.xsb");
+        for metric in [
+            Metric::Moves,
+            Metric::Pushes,
+            Metric::BoxLines,
+            Metric::BoxChanges,
+        ] {
+            let mut improved = 0;
+            for level in &levels[..30] {
+                let mut solver = Solver::new(
+                    level.map().clone(),
+                    Strategy::Fast,
+                    LowerBoundMethod::MinimumMove,
+                );
+                let solution = solver.search(Duration::from_secs(10)).unwrap();
+                let metrics = Metrics::new(level.map(), &solution).unwrap();
+
+                let optimizer = Optimizer::new(level.map().clone(), metric);
+                let Some(optimized) = optimizer.optimize(&solution, Duration::from_secs(10)) else {
+                    continue;
+                };
+                assert_solution(level.map(), &optimized);
+                let optimized_metrics = Metrics::new(level.map(), &optimized).unwrap();
+                assert!(optimized_metrics.key(metric) < metrics.key(metric));
+                improved += 1;
+            }
+            println!("{metric:?}: {improved} solutions improved");
+            assert!(improved > 0);
+        }
+    }
+
+    #[test]
+    #[cfg(not(debug_assertions))]
+    fn best_move_and_push_solutions_are_kept_apart() {
+        let levels = load_levels("assets/levels/microban_155.xsb");
+        let database = database_with_levels(&levels);
+
+        let level = &levels[14];
+        let level_id = database.get_level_id(level).unwrap();
+        let push_optimal = Solver::new(
+            level.map().clone(),
+            Strategy::OptimalPushMove,
+            LowerBoundMethod::MinimumPush,
+        )
+        .search(Duration::from_secs(10))
+        .unwrap();
+        let move_optimal = Solver::new(
+            level.map().clone(),
+            Strategy::OptimalMovePush,
+            LowerBoundMethod::MinimumPush,
+        )
+        .search(Duration::from_secs(10))
+        .unwrap();
+        assert!(move_optimal.moves() < push_optimal.moves());
+        assert!(move_optimal.pushes() > push_optimal.pushes());
+
+        database.update_solution(level_id, &push_optimal);
+        // Only the best move solution is replaced.
+        database.update_solution(level_id, &move_optimal);
+        assert_eq!(database.best_move_solution(level_id), Some(move_optimal));
+        assert_eq!(database.best_push_solution(level_id), Some(push_optimal));
+    }
+
+    #[test]
+    #[cfg(not(debug_assertions))]
+    fn best_solutions_are_saved_per_metric() {
+        let levels = load_levels("assets/levels/microban_155.xsb");
+        let database = database_with_levels(&levels);
+
+        let level = &levels[14];
+        let level_id = database.get_level_id(level).unwrap();
+        let mut solutions: Vec<_> = [Strategy::OptimalPushMove, Strategy::OptimalMovePush]
+            .into_iter()
+            .map(|strategy| {
+                Solver::new(level.map().clone(), strategy, LowerBoundMethod::MinimumPush)
+                    .search(Duration::from_secs(10))
+                    .unwrap()
+            })
+            .collect();
+        for metric in [Metric::BoxLines, Metric::BoxChanges] {
+            assert_eq!(database.best_metric_solution(level_id, metric), None);
+            for _ in 0..2 {
+                for solution in &solutions {
+                    database.update_metric_solution(level_id, metric, solution);
+                }
+                let best = database.best_metric_solution(level_id, metric).unwrap();
+                let best_key = Metrics::new(level.map(), &best).unwrap().key(metric);
+                assert!(solutions.iter().all(|solution| {
+                    best_key <= Metrics::new(level.map(), solution).unwrap().key(metric)
+                }));
+                solutions.reverse();
+            }
+        }
+    }
+
+    #[test]
+    #[cfg(not(debug_assertions))]
+    fn deadlock_patterns_are_persisted() {
+        let levels = load_levels("assets/levels/microban_155.xsb");
+        let database = database_with_levels(&levels);
+
+        let level = &levels[119];
+        let level_id = database.get_level_id(level).unwrap();
+        let mut solver = Solver::new(
+            level.map().clone(),
+            Strategy::OptimalPushMove,
+            LowerBoundMethod::MinimumPush,
+        );
+        let expected = solver.search(Duration::from_secs(10)).unwrap();
+        let patterns = solver
+            .deadlock_patterns()
+            .read()
+            .unwrap()
+            .patterns()
+            .to_vec();
+        assert!(!patterns.is_empty());
+        database.update_deadlock_patterns(level_id, &patterns);
+        assert_eq!(
+            database
+                .deadlock_patterns(level_id)
+                .into_iter()
+                .collect::<HashSet<_>>(),
+            patterns.into_iter().collect::<HashSet<_>>()
+        );
+
+        let mut solver = Solver::new(
+            level.map().clone(),
+            Strategy::OptimalPushMove,
+            LowerBoundMethod::MinimumPush,
+        );
+        solver.add_deadlock_patterns(database.deadlock_patterns(level_id));
+        let solution = solver.search(Duration::from_secs(10)).unwrap();
+        assert!(solver.pruning_stats().pattern_deadlocks > 0);
+        assert_eq!(solution.pushes(), expected.pushes());
+    }
+
+    #[test]
+    #[cfg(not(debug_assertions))]
+    fn solver_stats_are_consistent() {
+        let levels = load_levels("assets/levels/microban_155.xsb");
+        for strategy in [
+            Strategy::Fast,
+            Strategy::OptimalPushMove,
+            Strategy::IterativeDeepening,
+            Strategy::FeatureSpace,
+        ] {
+            let mut solver = Solver::new(
+                levels[10].map().clone(),
+                strategy,
+                LowerBoundMethod::MinimumPush,
+            );
+            let stats = solver.stats();
+            assert_eq!(stats.expanded_nodes, 0);
+            assert!(stats.best_lower_bound.is_some());
+
+            solver.search(Duration::from_secs(10)).unwrap();
+            let stats = solver.stats();
+            assert!(stats.expanded_nodes > 0);
+            assert!(stats.generated_nodes >= stats.duplicates);
+            assert!(stats.visited_nodes > 0);
+            assert!(stats.memory_usage > 0);
+            assert!(!stats.elapsed.is_zero());
+        }
+    }
+
+    #[test]
+    #[cfg(not(debug_assertions))]
+    fn memory_limit_is_enforced() {
+        let levels = load_levels("assets/levels/box_world_100.xsb");
+        for strategy in [Strategy::Fast, Strategy::OptimalPushMove] {
+            let mut solver = Solver::new(
+                levels[20].map().clone(),
+                strategy,
+                LowerBoundMethod::MinimumPush,
+            );
+            solver.set_memory_limit(Some(1024 * 1024));
+            assert_eq!(
+                solver.search(Duration::from_secs(60)),
+                Err(SolveError::OutOfMemory)
+            );
+        }
+
+        let levels = load_levels("assets/levels/microban_155.xsb");
+        for level in &levels[..30] {
+            let mut solver = Solver::new(
+                level.map().clone(),
+                Strategy::Beam,
+                LowerBoundMethod::MinimumPush,
+            );
+            solver.set_memory_limit(Some(64 * 1024 * 1024));
+            let solution = solver.search(Duration::from_secs(10)).unwrap();
+
+            assert_solution(level.map(), &solution);
+        }
+
+        // An anytime search running out of memory keeps its best solution.
+        let mut solver = Solver::new(
+            levels[99].map().clone(),
+            Strategy::Weighted { weight: 8 },
+            LowerBoundMethod::MinimumPush,
+        );
+        solver.set_anytime(true);
+        let first_solution = loop {
+            let result = solver.search(Duration::from_millis(1));
+            if let Some(solution) = solver.improved_solution() {
+                break solution;
+            }
+            assert_eq!(result, Err(SolveError::Timeout));
+        };
+        let memory_limit = solver.stats().memory_usage + 1;
+        solver.set_memory_limit(Some(memory_limit));
+        let solution = solver.search(Duration::from_secs(10)).unwrap();
+        assert!(solver.stats().memory_usage > memory_limit);
+        assert!(solution.moves() <= first_solution.moves());
+        assert_solution(levels[99].map(), &solution);
+    }
+
+    #[test]
+    #[cfg(not(debug_assertions))]
+    fn hinted_solutions_are_flagged() {
+        let levels = load_levels("assets/levels/microban_155.xsb");
+        let database = database_with_levels(&levels);
+
+        let level = &levels[10];
+        let level_id = database.get_level_id(level).unwrap();
+        let mut solver = Solver::new(
+            level.map().clone(),
+            Strategy::OptimalMovePush,
+            LowerBoundMethod::MinimumMove,
+        );
+        let optimal_solution = solver.search(Duration::from_secs(10)).unwrap();
+
+        // Play a few pushes, then ask for a hint from the current position.
+        let mut board = Board::with_map(level.map().clone());
+        let first_pushes = optimal_solution
+            .iter()
+            .scan(0, |pushes, action| {
+                *pushes += action.is_push() as usize;
+                Some(*pushes)
+            })
+            .take_while(|&pushes| pushes <= 2)
+            .count();
+        for action in optimal_solution.iter().take(first_pushes) {
+            board.do_action(action.direction());
+        }
+        database.record_hint(level_id);
+        let mut solver = Solver::new(
+            board.map.clone(),
+            Strategy::Fast,
+            LowerBoundMethod::MinimumMove,
+        );
+        let hint = solver.search(Duration::from_secs(10)).unwrap();
+        assert!(hint.iter().any(|action| action.is_push()));
+        for action in &*hint {
+            board.do_action(action.direction());
+        }
+        assert!(board.is_solved());
+
+        database.update_solution(level_id, board.actions());
+        database.flag_hinted_solution(level_id, board.actions());
+        assert!(database.is_best_move_solution_hinted(level_id));
+
+        if optimal_solution.moves() < board.actions().moves() {
+            database.update_solution(level_id, &optimal_solution);
+            assert!(!database.is_best_move_solution_hinted(level_id));
+        }
+    }
+
+    #[test]
+    #[cfg(not(debug_assertions))]
+    fn deadlocks_are_detected() {
+        let levels = load_levels("assets/levels/microban_155.xsb");
+        // Solutions never pass through deadlocks.
+        for level in &levels[..20] {
+            let mut solver = Solver::new(
+                level.map().clone(),
+                Strategy::Fast,
+                LowerBoundMethod::MinimumPush,
+            );
+            let solution = solver.search(Duration::from_secs(10)).unwrap();
+            let mut board = Board::with_map(level.map().clone());
+            for action in &*solution {
+                board.do_action(action.direction());
+                assert!(
+                    solver
+                        .deadlocked_boxes(
+                            board.map.player_position(),
+                            board.map.box_positions(),
+                            true
+                        )
+                        .is_empty()
+                );
+            }
+        }
+
+        let level = Level::from_str(
+            r#"
+######
+#@   #
+#  $ #
+# $.##
+#  .#
+#####
+"#,
+        )
+        .unwrap();
+        let solver = Solver::new(
+            level.map().clone(),
+            Strategy::Fast,
+            LowerBoundMethod::MinimumPush,
+        );
+        let mut board = Board::with_map(level.map().clone());
+        assert!(
+            solver
+                .deadlocked_boxes(board.map.player_position(), board.map.box_positions(), true)
+                .is_empty()
+        );
+        // Push the upper box next to the walls, where it can not be pushed again.
+        for direction in [Direction::Down, Direction::Right, Direction::Right] {
+            board.do_action(direction);
+        }
+        assert_eq!(
+            solver.deadlocked_boxes(
+                board.map.player_position(),
+                board.map.box_positions(),
+                false
+            ),
+            HashSet::from([Vector2::new(4, 2)])
+        );
+    }
+
+    #[test]
+    #[cfg(not(debug_assertions))]
+    fn goal_rooms_are_packed_in_order() {
+        let level = Level::from_str(
+            r#"
+#######
+#.. ..#
+#     #
+#     #
+### ###
+#     #
+# $$  #
+#  $$ #
+#  @  #
+#######
+"#,
+        )
+        .unwrap();
+        let mut solver = Solver::new(
+            level.map().clone(),
+            Strategy::Fast,
+            LowerBoundMethod::MinimumMove,
+        );
+        let goal_room = solver.goal_room().unwrap();
+        assert_eq!(goal_room.entrance, Vector2::new(3, 3));
+        let packing_order: HashSet<_> = (0..4).map(|i| goal_room.goal_macro(i).goal).collect();
+        assert_eq!(&packing_order, level.map().goal_positions());
+        let solution = solver.search(Duration::from_secs(10)).unwrap();
+        assert!(Metrics::new(level.map(), &solution).is_some());
+
+        // Goal macros may discard optimal solutions.
+        let solver = Solver::new(
+            level.map().clone(),
+            Strategy::OptimalPushMove,
+            LowerBoundMethod::MinimumMove,
+        );
+        assert!(solver.goal_room().is_none());
+
+        // The goals of the first Microban level are not in a single room.
+        let level = load_levels("assets/levels/microban_155.xsb").remove(0);
+        let solver = Solver::new(
+            level.map().clone(),
+            Strategy::Fast,
+            LowerBoundMethod::MinimumMove,
+        );
+        assert!(solver.goal_room().is_none());
+    }
+
+    #[test]
+    #[cfg(not(debug_assertions))]
+    fn tunnels_are_detected() {
+        let no_boxes = |_| false;
+
+        // A straight tunnel, the box is only pushed further once the player
+        // is inside.
+        let level = Level::from_str(
+            r#"
+###########
+#   ###   #
+# $       #
+#@  ###  .#
+###########
+"#,
+        )
+        .unwrap();
+        let solver = Solver::new(
+            level.map().clone(),
+            Strategy::OptimalMovePush,
+            LowerBoundMethod::MinimumMove,
+        );
+        let tunnels = solver.tunnels();
+        assert!(!tunnels.continues_push(Vector2::new(4, 2), Direction::Right, no_boxes));
+        assert!(tunnels.continues_push(Vector2::new(5, 2), Direction::Right, no_boxes));
+        assert!(tunnels.continues_push(Vector2::new(6, 2), Direction::Right, no_boxes));
+        // The box can not leave the tunnel sideways, since the squares next to
+        // it are corners.
+        assert!(tunnels.continues_push(Vector2::new(7, 2), Direction::Right, no_boxes));
+        assert!(!tunnels.continues_push(Vector2::new(8, 2), Direction::Right, no_boxes));
+        assert!(tunnels.continues_push(Vector2::new(5, 2), Direction::Left, no_boxes));
+
+        // A one-way corridor of goals, boxes on its goals are pushed further
+        // until the goals behind them are filled.
+        let level = Level::from_str(
+            r#"
+##########
+#     ####
+# $ $ ...#
+#@ $ #####
+#    #
+######
+"#,
+        )
+        .unwrap();
+        let solver = Solver::new(
+            level.map().clone(),
+            Strategy::OptimalMovePush,
+            LowerBoundMethod::MinimumMove,
+        );
+        let tunnels = solver.tunnels();
+        assert!(tunnels.continues_push(Vector2::new(5, 2), Direction::Right, no_boxes));
+        assert!(tunnels.continues_push(Vector2::new(6, 2), Direction::Right, no_boxes));
+        assert!(
+            tunnels.continues_push(Vector2::new(6, 2), Direction::Right, |position| position
+                == Vector2::new(8, 2))
+        );
+        assert!(
+            !tunnels.continues_push(Vector2::new(7, 2), Direction::Right, |position| position
+                == Vector2::new(8, 2))
+        );
+        assert!(!tunnels.continues_push(Vector2::new(4, 2), Direction::Right, no_boxes));
+
+        // A turning corridor only the player can pass.
+        let level = Level::from_str(
+            r#"
+#######
+#@$ . #
+# #####
+#   #
+#####
+"#,
+        )
+        .unwrap();
+        let solver = Solver::new(
+            level.map().clone(),
+            Strategy::OptimalMovePush,
+            LowerBoundMethod::MinimumMove,
+        );
+        let tunnels = solver.tunnels();
+        for (x, y) in [(1, 2), (1, 3), (2, 3), (3, 3)] {
+            assert!(tunnels.is_player_tunnel(Vector2::new(x, y)));
+        }
+        assert!(!tunnels.is_player_tunnel(Vector2::new(3, 1)));
+        assert!(tunnels.continues_push(Vector2::new(2, 1), Direction::Right, no_boxes));
+    }
+
+    #[test]
+    #[cfg(not(debug_assertions))]
+    fn tunnels_keep_move_optimality() {
+        for level in [
+            r#"
+###########
+#   ###   #
+# $       #
+#@  ###  .#
+###########
+"#,
+            r#"
+##########
+#     ####
+# $ $ ...#
+#@ $ #####
+#    #
+######
+"#,
+            r#"
+#######
+#@$ . #
+# #####
+#   #
+#####
+"#,
+            // the player leaves through the player tunnel beside the box
+            r#"
+##########
+#   .    #
+#### #####
+#### #####
+#### #####
+#### #####
+#### #####
+#### #####
+####$  ###
+####@# ###
+###### $.#
+##########
+"#,
+        ] {
+            let level = Level::from_str(level).unwrap();
+            let mut solver = Solver::new(
+                level.map().clone(),
+                Strategy::OptimalMovePush,
+                LowerBoundMethod::MinimumMove,
+            );
+            let solution = solver.search(Duration::from_secs(10)).unwrap();
+            assert!(Metrics::new(level.map(), &solution).is_some());
+            assert_eq!(Some(solution.moves()), minimum_moves(level.map()));
+        }
+    }
+
+    #[test]
+    #[cfg(not(debug_assertions))]
+    fn boxes_frozen_on_goals_are_not_pushed() {
+        let level = Level::from_str(
+            r#"
+#######
+#* *  #
+#@  $.#
+#######
+"#,
+        )
+        .unwrap();
+        let solver = Solver::new(
+            level.map().clone(),
+            Strategy::Fast,
+            LowerBoundMethod::MinimumPush,
+        );
+        let boxes = BoxSet::from_positions(solver.floor(), level.map().box_positions());
+        let boxes_hash = solver.zobrist().boxes_hash(&boxes);
+        let state = State::new(level.map().player_position(), boxes, boxes_hash, &solver);
+        // The box in the middle can still be pushed sideways.
+        let frozen: Vec<_> = state
+            .frozen_boxes()
+            .iter()
+            .map(|index| solver.floor().position(index))
+            .collect();
+        assert_eq!(frozen, [Vector2::new(1, 1)]);
+        // Frozen boxes are kept apart from the other boxes.
+        assert_eq!(state.boxes.iter().count(), 2);
+        assert!(!state.has_box_at(Vector2::new(2, 1), &solver));
+        assert!(state.has_box_at(Vector2::new(1, 1), &solver));
+
+        for lower_bound_method in [
+            LowerBoundMethod::MinimumPush,
+            LowerBoundMethod::MinimumMatching,
+        ] {
+            let mut solver = Solver::new(level.map().clone(), Strategy::Fast, lower_bound_method);
+            let solution = solver.search(Duration::from_secs(10)).unwrap();
+            assert_eq!(solution.pushes(), 1);
+            assert!(solver.pruning_stats().frozen_boxes > 0);
+        }
+    }
+
+    #[test]
+    #[cfg(not(debug_assertions))]
+    fn levels_are_decomposed() {
+        // Boxes can not be pushed through the corridor between the rooms.
+        let level = Level::from_str(
+            r#"
+#########
+#   #   #
+# $.# . #
+#@  # $ #
+## ### ##
+ #     #
+ #######
+"#,
+        )
+        .unwrap();
+        let decomposition = Decomposition::new(level.map()).unwrap();
+        assert_eq!(decomposition.parts.len(), 2);
+        for part in &decomposition.parts {
+            assert_eq!(part.box_positions.len(), 1);
+            assert_eq!(part.goal_positions.len(), 1);
+            let box_position = part.box_positions.iter().next().unwrap();
+            let goal_position = part.goal_positions.iter().next().unwrap();
+            assert_eq!(box_position.x < 4, goal_position.x < 4);
+        }
+
+        let mut solver = Solver::new(
+            level.map().clone(),
+            Strategy::Fast,
+            LowerBoundMethod::MinimumMove,
+        );
+        solver.set_decomposition(true);
+        let solution = solver.search(Duration::from_secs(10)).unwrap();
+        assert!(Metrics::new(level.map(), &solution).is_some());
+        assert!(solver.stats().expanded_nodes > 0);
+
+        // Boxes which can be pushed to the same squares are in the same part.
+        let level = Level::from_str(
+            r#"
+########
+#@$ $..#
+########
+"#,
+        )
+        .unwrap();
+        assert!(Decomposition::new(level.map()).is_none());
+    }
+
+    #[test]
+    #[cfg(not(debug_assertions))]
+    fn solvers_are_driven_through_the_trait() {
+        let levels = load_levels("assets/levels/box_world_100.xsb");
+        let config = SolverConfig::default();
+
+        let worker = SolverWorker::spawn(config.create_solver(levels[0].map().clone(), Vec::new()));
+        let solution = loop {
+            if let Some(SolverMessage::Finished { result, stats }) = worker
+                .messages()
+                .into_iter()
+                .find(|message| matches!(message, SolverMessage::Finished { .. }))
+            {
+                assert!(stats.expanded_nodes > 0);
+                break result.unwrap();
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        };
+        assert_solution(levels[0].map(), &solution);
+
+        let mut solver = config.create_solver(levels[20].map().clone(), Vec::new());
+        solver.start();
+        assert_eq!(
+            solver.step(Duration::from_millis(10)),
+            Err(SolveError::Timeout)
+        );
+        solver.cancel();
+        assert_eq!(
+            solver.step(Duration::from_secs(60)),
+            Err(SolveError::Timeout)
+        );
+        assert!(solver.result().is_none());
+    }
+
+    #[test]
+    #[cfg(not(debug_assertions))]
+    fn anytime_search_improves_solutions() {
+        let levels = load_levels("assets/levels/microban_155.xsb");
+
+        // A weight of 1 gives the heuristic of `Mixed`.
+        let mut solver = Solver::new(
+            levels[19].map().clone(),
+            Strategy::Mixed,
+            LowerBoundMethod::MinimumPush,
+        );
+        let expected = solver.search(Duration::from_secs(10)).unwrap();
+        let mut solver = Solver::new(
+            levels[19].map().clone(),
+            Strategy::Weighted { weight: 1 },
+            LowerBoundMethod::MinimumPush,
+        );
+        let solution = solver.search(Duration::from_secs(10)).unwrap();
+        assert_eq!(solution.moves(), expected.moves());
+        assert_eq!(solution.pushes(), expected.pushes());
+
+        let database = database_with_levels(&levels);
+
+        let level = &levels[99];
+        let level_id = database.get_level_id(level).unwrap();
+        let config = SolverConfig {
+            strategy: Strategy::Weighted { weight: 8 },
+            lower_bound_method: LowerBoundMethod::MinimumPush,
+            anytime: true,
+            ..SolverConfig::default()
+        };
+        let worker = SolverWorker::spawn(config.create_solver(level.map().clone(), Vec::new()));
+        let mut improvements: Vec<Actions> = Vec::new();
+        let solution = 'search: loop {
+            for message in worker.messages() {
+                match message {
+                    SolverMessage::Improved { solution, .. } => {
+                        assert_solution(level.map(), &solution);
+                        assert!(
+                            improvements
+                                .last()
+                                .is_none_or(|previous| solution.moves() < previous.moves())
+                        );
+                        database.update_solution(level_id, &solution);
+                        improvements.push(solution);
+                    }
+                    SolverMessage::Finished { result, .. } => break 'search result.unwrap(),
+                    SolverMessage::Progress { .. } => {}
+                }
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        };
+        assert!(improvements.len() > 1);
+        assert_eq!(improvements.last(), Some(&solution));
+
+        // The last solution is move optimal.
+        for level in &levels[..5] {
+            let mut solver = Solver::new(
+                level.map().clone(),
+                Strategy::Weighted { weight: 8 },
+                LowerBoundMethod::MinimumPush,
+            );
+            solver.set_anytime(true);
+            let solution = solver.search(Duration::from_secs(10)).unwrap();
+            assert!(Metrics::new(level.map(), &solution).is_some());
+            assert_eq!(Some(solution.moves()), minimum_moves(level.map()));
+        }
+        assert_eq!(
+            database.best_move_solution(level_id).unwrap().moves(),
+            solution.moves()
+        );
+    }
+
+    #[test]
+    #[cfg(all(unix, not(debug_assertions)))]
+    fn external_solvers_are_validated() {
+        let levels = load_levels("assets/levels/microban_155.xsb");
+        let map = levels[0].map().clone();
+        // Fake solver which prints the given lines after reading the map.
+        let fake_solver = |script: &str| {
+            let config = SolverConfig {
+                backend: Backend::External,
+                external_command: "sh".to_string(),
+                external_args: vec!["-c".to_string(), format!("cat > /dev/null; {script}")],
+                ..Default::default()
+            };
+            let mut solver = config.create_solver(map.clone(), Vec::new());
+            solver.start();
+            solver
+        };
+
+        let mut solver = fake_solver(
+            "echo 'progress expanded_nodes=42 best_lower_bound=8'; echo dlurrrdlullddrulurUULDRDDRRULDLUU",
+        );
+        let solution = solver.step(Duration::from_secs(10)).unwrap();
+        assert_eq!(solution.to_string(), "dlUrrrdLullddrUluRuulDrddrruLdlUU");
+        assert_eq!(solver.stats().expanded_nodes, 42);
+        assert_eq!(solver.stats().best_lower_bound, Some(8));
+        assert_eq!(solver.result(), Some(Ok(solution)));
+
+        let mut solver = fake_solver("echo dlUrrrdLullddrUluRuulDrddrruLdlU");
+        assert_eq!(
+            solver.step(Duration::from_secs(10)),
+            Err(SolveError::NoSolution)
+        );
+        let mut solver = fake_solver("exit 1");
+        assert_eq!(
+            solver.step(Duration::from_secs(10)),
+            Err(SolveError::NoSolution)
+        );
+        let mut solver = SolverConfig {
+            backend: Backend::External,
+            external_command: "/nonexistent/solver".to_string(),
+            ..Default::default()
+        }
+        .create_solver(map.clone(), Vec::new());
+        solver.start();
+        assert_eq!(
+            solver.step(Duration::from_secs(10)),
+            Err(SolveError::SpawnFailed)
+        );
+
+        // The program is killed once the time limit is exceeded.
+        let time_limited_solver = |script: &str| {
+            let mut solver = ExternalSolver::new(
+                map.clone(),
+                "sh".to_string(),
+                vec!["-c".to_string(), format!("cat > /dev/null; {script}")],
+            );
+            solver.set_time_limit(Some(Duration::from_millis(500)));
+            solver.start();
+            solver
+        };
+        let timer = std::time::Instant::now();
+        let mut solver = time_limited_solver("echo dlurrrdlullddrulurUULDRDDRRULDLUU; sleep 10");
+        let solution = solver.step(Duration::from_secs(10)).unwrap();
+        assert_eq!(solution.to_string(), "dlUrrrdLullddrUluRuulDrddrruLdlUU");
+        let mut solver = time_limited_solver("sleep 10");
+        assert_eq!(
+            solver.step(Duration::from_secs(10)),
+            Err(SolveError::Timeout)
+        );
+        assert_eq!(solver.result(), Some(Err(SolveError::Timeout)));
+        assert!(timer.elapsed() < Duration::from_secs(5));
+
+        let timer = std::time::Instant::now();
+        let mut solver = fake_solver("sleep 10");
+        assert_eq!(
+            solver.step(Duration::from_millis(100)),
+            Err(SolveError::Timeout)
+        );
+        solver.cancel();
+        assert_eq!(
+            solver.step(Duration::from_secs(10)),
+            Err(SolveError::Timeout)
+        );
+        assert!(solver.result().is_none());
+        assert!(timer.elapsed() < Duration::from_secs(5));
+    }
+
+    #[test]
+    #[cfg(not(debug_assertions))]
+    fn benchmark_reports_flag_regressions() {
+        let levels = load_levels("assets/levels/microban_155.xsb");
+        let benchmark = Benchmark {
+            strategy: Strategy::Fast,
+            lower_bound_method: LowerBoundMethod::MinimumMove,
+            time_limit: Duration::from_secs(10),
+        };
+        let report = BenchmarkReport {
+            results: benchmark.run_collection("microban_155.xsb", &levels[..5]),
+        };
+        assert!(report.results.iter().all(|result| result.is_solved()));
+        assert_eq!(BenchmarkReport::from_csv(&report.to_csv()).unwrap(), report);
+        let json: serde_json::Value = serde_json::from_str(&report.to_json()).unwrap();
+        assert_eq!(json.as_array().unwrap().len(), report.results.len());
+        assert_eq!(json[0]["collection"], "microban_155.xsb");
+        assert_eq!(json[0]["status"], "solved");
+        assert!(report.compare(&report, 0, Duration::ZERO).is_empty());
+
+        let mut baseline = report.clone();
+        baseline.results[0].moves = Some(1);
+        baseline.results[1].elapsed = Duration::from_micros(1);
+        let mut result = report.clone();
+        result.results[2].error = Some(SolveError::Timeout);
+        let regressions = result.compare(&baseline, 20, Duration::ZERO);
+        assert!(matches!(
+            regressions[..],
+            [
+                Regression::WorseSolution { .. },
+                Regression::Slower { .. },
+                Regression::Failed { .. }
+            ]
+        ));
+        assert!(
+            result
+                .compare(&baseline, 20, Duration::from_secs(1))
+                .iter()
+                .all(|regression| !matches!(regression, Regression::Slower { .. }))
+        );
+    }
+
+    /// Runs the solver over every level collection and writes the reports to
+    /// `target/benchmark/`. If `benches/baseline.csv` exists, the run fails on
+    /// regressions against it.
+    ///
+    /// `cargo test --release -- --ignored benchmark_level_collections --nocapture`
+    #[test]
+    #[ignore = "takes a long time"]
+    #[cfg(not(debug_assertions))]
+    fn benchmark_level_collections() {
+        let env_var = |name: &str, default: u64| {
+            std::env::var(name).map_or(default, |value| value.parse().unwrap())
+        };
+        let benchmark = Benchmark {
+            strategy: Strategy::Fast,
+            lower_bound_method: LowerBoundMethod::MinimumMove,
+            time_limit: Duration::from_secs(env_var("BENCHMARK_TIME_LIMIT", 10)),
+        };
+        let report = benchmark.run(Path::new("assets/levels")).unwrap();
+        print!("{report}");
+
+        fs::create_dir_all("target/benchmark").unwrap();
+        fs::write("target/benchmark/report.csv", report.to_csv()).unwrap();
+        fs::write("target/benchmark/report.json", report.to_json()).unwrap();
+
+        let Ok(baseline) = fs::read_to_string("benches/baseline.csv") else {
+            return;
+        };
+        let baseline = BenchmarkReport::from_csv(&baseline).unwrap();
+        let regressions = report.compare(
+            &baseline,
+            env_var("BENCHMARK_MAX_SLOWDOWN", 20) as u32,
+            Duration::from_millis(100),
+        );
+        for regression in &regressions {
+            println!("{regression}");
+        }
+        assert!(regressions.is_empty());
+    }
+
     // #[bench]
     // fn bench_solver(b: &mut Bencher) {
     //     let levels = Level::load_from_file(Path::new("assets/levels/box_world_100.xsb")).unwrap();